@@ -0,0 +1,166 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::env;
+
+use serde_json;
+
+use rt_result::RtResult;
+use config::CleanConfig;
+use dependencies::workspace_member_dirs;
+use dirs::rusty_tags_dir;
+use types::CacheLock;
+
+/// Removes the cached tags in the rusty-tags directory - everything beside of
+/// the 'config.toml' and the locks - and with 'config.project' also the tags
+/// files of the cargo project. The cache lock is held while cleaning, so the
+/// cached tags aren't removed under another running rusty-tags.
+pub fn clean(config: &CleanConfig) -> RtResult<()> {
+    let _cache_lock = match CacheLock::try_acquire()? {
+        Some(lock) => lock,
+        None       => return Err("Another rusty-tags is updating the cached tags, so they can't be cleaned now!".into())
+    };
+
+    let mut paths = cached_paths(rusty_tags_dir()?)?;
+
+    if config.project {
+        env::set_current_dir(&config.start_dir)?;
+        for dir in workspace_member_dirs(&workspace_metadata(config)?)? {
+            for name in &config.tags_file_names {
+                let tags_file = dir.join(name);
+                if tags_file.is_file() {
+                    paths.push(tags_file);
+                }
+            }
+        }
+    }
+
+    if paths.is_empty() {
+        if ! config.quiet {
+            println!("Nothing to clean");
+        }
+
+        return Ok(());
+    }
+
+    if ! config.force {
+        println!("Removing:");
+        for path in &paths {
+            println!("   {}", path.display());
+        }
+
+        if ! confirm("Continue?")? {
+            return Ok(());
+        }
+    }
+
+    let mut num_files = 0;
+    let mut num_bytes = 0;
+    for path in &paths {
+        let (files, bytes) = remove_path(path)?;
+        num_files += files;
+        num_bytes += bytes;
+    }
+
+    if ! config.quiet {
+        println!("Removed {} files with {} bytes", num_files, num_bytes);
+    }
+
+    Ok(())
+}
+
+/// The paths in the rusty-tags directory `rusty_tags_dir` which are removed
+/// by the cleaning, everything beside of the 'config.toml' and the locks.
+fn cached_paths(rusty_tags_dir: &Path) -> RtResult<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(rusty_tags_dir)? {
+        let path = entry?.path();
+        if path.file_name().map(|n| n != "config.toml" && n != "locks").unwrap_or(false) {
+            paths.push(path);
+        }
+    }
+
+    paths.sort();
+    Ok(paths)
+}
+
+/// Removes the file or directory at `path` and returns the
+/// number of removed files and their size in bytes.
+fn remove_path(path: &Path) -> RtResult<(u64, u64)> {
+    let metadata = fs::symlink_metadata(path)?;
+    if ! metadata.is_dir() {
+        fs::remove_file(path)?;
+        return Ok((1, metadata.len()));
+    }
+
+    let mut num_files = 0;
+    let mut num_bytes = 0;
+    for entry in fs::read_dir(path)? {
+        let (files, bytes) = remove_path(&entry?.path())?;
+        num_files += files;
+        num_bytes += bytes;
+    }
+
+    fs::remove_dir(path)?;
+    Ok((num_files, num_bytes))
+}
+
+/// Asks the user the yes/no `question`, everything but 'y' or 'yes' is a no.
+fn confirm(question: &str) -> RtResult<bool> {
+    print!("{} [y/N] ", question);
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    let answer = answer.trim().to_lowercase();
+    Ok(answer == "y" || answer == "yes")
+}
+
+/// The metadata of the workspace members, without the dependencies, so nothing is fetched.
+fn workspace_metadata(config: &CleanConfig) -> RtResult<serde_json::Value> {
+    let mut cmd = Command::new("cargo");
+    cmd.arg("metadata")
+        .arg("--format-version=1")
+        .arg("--no-deps");
+
+    if let Some(ref manifest_path) = config.manifest_path {
+        cmd.arg("--manifest-path").arg(manifest_path);
+    }
+
+    let output = cmd.output()
+        .map_err(|err| format!("'cargo' execution failed: {}\nIs 'cargo' correctly installed?", err))?;
+
+    if ! output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).into_owned().into());
+    }
+
+    Ok(serde_json::from_slice(&output.stdout)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use test_support::{test_dir, write_file};
+    use super::*;
+
+    #[test]
+    fn cached_paths_keep_the_config_and_the_locks() {
+        let dir = test_dir("cached_paths_keep_the_config_and_the_locks");
+        write_file(dir.join("config.toml"), "");
+        write_file(dir.join("locks").join("cache.lock"), "1");
+        write_file(dir.join("cache").join("serde-1.0.0-1234.vi"), "");
+        write_file(dir.join("std-lib-hint-shown"), "");
+
+        assert_eq!(cached_paths(&dir).unwrap(), vec![dir.join("cache"), dir.join("std-lib-hint-shown")]);
+    }
+
+    #[test]
+    fn cache_lock_is_only_acquired_once() {
+        let lock = CacheLock::try_acquire().unwrap();
+        assert!(lock.is_some());
+        assert!(CacheLock::try_acquire().unwrap().is_none());
+
+        drop(lock);
+        assert!(CacheLock::try_acquire().unwrap().is_some());
+    }
+}
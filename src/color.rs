@@ -0,0 +1,133 @@
+use std::env;
+use std::fmt::Display;
+use std::io::{self, IsTerminal};
+use std::str::FromStr;
+
+/// When the status output is colored, set by '--color'.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ColorChoice {
+    Always,
+
+    /// only on a terminal and if 'NO_COLOR' isn't set
+    Auto,
+
+    Never
+}
+
+impl ColorChoice {
+    pub fn names() -> &'static [&'static str] {
+        &["always", "auto", "never"]
+    }
+
+    /// If the output to stderr - where all status output with colors
+    /// is written to - gets colored.
+    pub fn colors_stderr(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never  => false,
+            ColorChoice::Auto   => {
+                env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()) && io::stderr().is_terminal()
+            }
+        }
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ColorChoice, String> {
+        match s.to_lowercase().as_str() {
+            "always" => Ok(ColorChoice::Always),
+            "auto"   => Ok(ColorChoice::Auto),
+            "never"  => Ok(ColorChoice::Never),
+            _        => Err(format!("Invalid color choice '{}', valid values are: {}", s, ColorChoice::names().join(", ")))
+        }
+    }
+}
+
+/// The parts of the status output which are colored.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Style {
+    /// the names of the sources
+    Name,
+
+    /// the sources with up-to-date tags
+    Skipped,
+
+    /// the sources which couldn't be found
+    Missing,
+
+    /// the failed sources and errors
+    Error
+}
+
+impl Style {
+    /// 'text' with the color of the style if 'colored', otherwise 'text' as it is.
+    pub fn paint<T: Display>(self, colored: bool, text: T) -> String {
+        if ! colored {
+            return text.to_string();
+        }
+
+        format!("\x1b[{}m{}\x1b[0m", THEME.color(self), text)
+    }
+}
+
+/// The colors of the styles as SGR parameters, e.g. '1;31' for bold red. The defaults
+/// can be changed by the environment variable 'RUSTY_TAGS_COLORS', which contains
+/// colon separated assignments, like 'name=36:skipped=2:missing=33:error=1;31'.
+struct Theme {
+    name: String,
+    skipped: String,
+    missing: String,
+    error: String
+}
+
+impl Theme {
+    fn from_env() -> Theme {
+        let mut theme = Theme {
+            name: "1".to_string(),
+            skipped: "2".to_string(),
+            missing: "33".to_string(),
+            error: "1;31".to_string()
+        };
+
+        if let Ok(colors) = env::var("RUSTY_TAGS_COLORS") {
+            for assignment in colors.split(':') {
+                let mut parts = assignment.splitn(2, '=');
+                let (style, color) = match (parts.next(), parts.next()) {
+                    (Some(style), Some(color)) if is_sgr_color(color) => (style.trim(), color.trim().to_string()),
+                    _ => continue
+                };
+
+                match style {
+                    "name"    => theme.name = color,
+                    "skipped" => theme.skipped = color,
+                    "missing" => theme.missing = color,
+                    "error"   => theme.error = color,
+                    _         => {}
+                }
+            }
+        }
+
+        theme
+    }
+
+    fn color(&self, style: Style) -> &str {
+        match style {
+            Style::Name    => &self.name,
+            Style::Skipped => &self.skipped,
+            Style::Missing => &self.missing,
+            Style::Error   => &self.error
+        }
+    }
+}
+
+/// If 'color' only consists of SGR parameters, so no other escape codes get into the output.
+fn is_sgr_color(color: &str) -> bool {
+    let color = color.trim();
+    ! color.is_empty() && color.chars().all(|c| c.is_ascii_digit() || c == ';')
+}
+
+lazy_static! {
+    static ref THEME: Theme = Theme::from_env();
+}
@@ -1,68 +1,781 @@
 use std::env;
+use std::ffi::OsString;
 use std::path::{Path, PathBuf};
-use std::fs::File;
-use std::io::Read;
+use std::fs::{self, File};
+use std::io::{self, Read};
 use std::cmp::max;
 use std::process::Command;
-use clap::App;
+use std::time::Duration;
+use clap::{App, AppSettings, Arg, ArgMatches, Shell, SubCommand};
 use toml;
 use num_cpus;
-use types::{TagsExe, TagsKind, TagsSpec};
-use rt_result::RtResult;
+use types::{TagsExe, TagsKind, TagsSort, TagsSpec};
+use rt_result::{RtResult, RtErr};
 use dirs;
+use tags::check_tags_file;
+use color::ColorChoice;
+use glob;
+use log::debug;
 
 /// the configuration used to run rusty-tags
+#[derive(Clone)]
 pub struct Config {
-    /// the tags that should be created
+    /// the tags that are currently created, one of 'tags_specs'
     pub tags_spec: TagsSpec,
 
+    /// all tags that should be created
+    pub tags_specs: Vec<TagsSpec>,
+
     /// start directory for the search of the 'Cargo.toml'
     pub start_dir: PathBuf,
 
+    /// the 'Cargo.toml' of the cargo project, if not given cargo searches it
+    /// upwards from 'start_dir'
+    pub manifest_path: Option<PathBuf>,
+
+    /// directory where the tags files of the cargo project roots are written,
+    /// if not given they're written beside of the 'Cargo.toml' of each root
+    pub output_dir: Option<PathBuf>,
+
+    /// write the tags of the cargo project roots to stdout instead of into
+    /// files, all other output is written to stderr
+    pub tags_to_stdout: bool,
+
+    /// print a summary of the run as json to stdout
+    pub summary_json: bool,
+
+    /// write all output besides of the tags or the summary to stderr,
+    /// if the tags or the summary are written to stdout
+    pub output_to_stderr: bool,
+
+    /// write the tags of all workspace members and of their dependencies
+    /// into a single tags file at the workspace root
+    pub workspace_tags: bool,
+
+    /// write the tags of all roots and of their dependencies only into this file,
+    /// instead of the tags files of the roots and in the dependency source directories
+    pub single_file: Option<PathBuf>,
+
+    /// don't merge the tags of the dependencies into the tags of the roots, but
+    /// print the paths of the separate tags files of all sources
+    pub no_merge: bool,
+
+    /// don't fetch the sources of the dependencies, only
+    /// the already downloaded sources are used
+    pub no_fetch: bool,
+
     /// do not generate tags for dependencies
     pub omit_deps: bool,
 
+    /// also generate tags for the dev dependencies
+    pub dev_deps: bool,
+
+    /// also generate tags for the build dependencies
+    pub build_deps: bool,
+
+    /// don't generate tags for these dependencies, given
+    /// by their name or by 'name@version'
+    pub exclude_deps: Vec<String>,
+
+    /// only generate tags for these dependencies, given
+    /// by their name or by 'name@version'
+    pub only_deps: Vec<String>,
+
+    /// the crates read by '--crates-from', given by their name or by
+    /// 'name@version', which are the only dependencies getting tags,
+    /// even if they're indirect dependencies
+    pub crates: Option<Vec<String>>,
+
+    /// only create the tags of this dependency of the cargo project, given
+    /// by its name or by 'name@version', instead of the ones of the roots
+    pub dep: Option<String>,
+
+    /// the files changed since the git ref of '--since', then only the roots and path
+    /// dependencies containing one of them are recreated, and their dependent sources
+    pub changed_since: Option<Vec<PathBuf>>,
+
+    /// how many levels of dependencies below the roots get tags, 0 only
+    /// tags the roots and 1 their direct dependencies, none tags all
+    pub depth: Option<usize>,
+
+    /// the cargo features to activate, which determine
+    /// the included optional dependencies
+    pub features: Vec<String>,
+
+    /// don't activate the 'default' feature
+    pub no_default_features: bool,
+
+    /// activate all available features
+    pub all_features: bool,
+
+    /// only include the target specific dependencies of this target
+    /// triple, if not given the target of the host is used
+    pub target: Option<String>,
+
+    /// include the target specific dependencies of all targets
+    pub all_targets: bool,
+
+    /// the directories of the additional targets of the roots - like
+    /// 'examples' - relative to their 'Cargo.toml', which are also tagged
+    pub local_target_dirs: Vec<&'static str>,
+
+    /// the file name of the tags files of the roots given by '--tags-file-name',
+    /// instead of the file name of 'tags_spec', only for a single kind of tags
+    pub tags_file_name: Option<String>,
+
     /// forces the recreation of cached tags
     pub force_recreate: bool,
 
+    /// hash the contents instead of the modification times of the source
+    /// files, to detect changes of the roots and path dependencies
+    pub strict_hash: bool,
+
+    /// keep the tags of the existing tags files of the roots,
+    /// which weren't created by rusty-tags
+    pub append: bool,
+
+    /// additional tags files, which are merged into the
+    /// tags files of the roots
+    pub extra_tags_files: Vec<PathBuf>,
+
+    /// write the paths of the source files into the tags
+    /// relative to the directory of the tags file
+    pub relative_paths: bool,
+
+    /// resolve the symlinks of the source directories before creating their tags
+    pub canonicalize: bool,
+
+    /// also search hidden directories for source files, which
+    /// are used to detect changes and by gtags and cscope
+    pub include_hidden: bool,
+
+    /// write only the first of the merged vi tags with the same
+    /// name, file and pattern
+    pub dedup_tags: bool,
+
+    /// how the lines of the vi tags are sorted
+    pub tags_sort: TagsSort,
+
+    /// fail instead of warning if a created tags file of a root is invalid
+    /// or if the 'post_hook' failed
+    pub strict: bool,
+
+    /// the shell command run after the tags file of a root
+    /// or of the workspace was written
+    pub post_hook: Option<String>,
+
+    /// only report which tags would be created, without creating them
+    pub dry_run: bool,
+
+    /// continue with the other sources if the tags creation of a source failed
+    pub keep_going: bool,
+
+    /// fail if the sources of some dependencies couldn't be found
+    pub fail_on_missing: bool,
+
     /// verbose output about all operations
     pub verbose: bool,
 
+    /// the level of the verbose output, 1 for '-v' and
+    /// 2 for '-vv', which additionally outputs the stderr
+    /// of successful ctags executions
+    pub verbosity: u64,
+
     /// don't output anything but errors
     pub quiet: bool,
 
+    /// color the status output to stderr, resolved from '--color'
+    pub colored: bool,
+
+    /// don't output any warnings, not even with 'quiet'
+    pub no_warnings: bool,
+
     /// num threads used for the tags creation
-    pub num_threads: u32
+    pub num_threads: u32,
+
+    /// the time limit for each execution of cargo and ctags
+    pub timeout: Option<Duration>,
+
+    /// how often fetching the source and metadata is retried on failures
+    pub fetch_retries: u32,
+
+    /// don't lock the cache directory while updating the tags
+    pub no_lock: bool,
+
+    /// put the cached tags of the sources into the target directory of the cargo project
+    pub artifacts_in_target: bool,
+
+    /// the directory of the cached tags of the sources, if not given it's the cache
+    /// directory, with 'artifacts_in_target' it's set after reading the metadata
+    pub artifacts_dir: Option<PathBuf>,
+
+    /// how long to wait for the lock of the cache directory,
+    /// which is held by another running rusty-tags
+    pub lock_timeout: Duration,
+
+    /// after the tags creation watch the source files and update the tags on changes
+    pub watch: bool,
+
+    /// create the tags of the rust standard library
+    pub std_lib_tags: bool,
+
+    /// never hint about the missing source of the standard library
+    pub ignore_std_missing: bool,
+
+    /// only (re)create the tags of the standard library, without touching the cargo project
+    pub std_only: bool,
+
+    /// the configurations of the cargo projects matched by '--projects', which
+    /// are updated one after another instead of the project of 'start_dir'
+    pub projects: Vec<Config>
+}
+
+/// what rusty-tags should do
+pub enum Action {
+    /// create the tags, the default if no subcommand is given
+    UpdateTags(Box<Config>),
+
+    /// remove the cached tags, the 'clean' subcommand
+    Clean(CleanConfig),
+
+    /// print the resolved dependencies, the 'deps' subcommand
+    Deps(Box<DepsConfig>),
+
+    /// print the completion script for a shell, the 'completions' subcommand
+    Completions(Shell),
+
+    /// print the paths one per line, by '--print-cache-dir' and '--print-std-tags-path'
+    PrintPaths(Vec<PathBuf>)
+}
+
+impl Action {
+    /// The action of the command line arguments of the process, exits the
+    /// process for invalid arguments or if the help or version are requested.
+    pub fn from_command_args() -> RtResult<Action> {
+        Action::from_matches(&app().get_matches())
+    }
+
+    /// The action of the command line arguments 'args', which start with the
+    /// name of the binary like the arguments of the process.
+    pub fn from_args<I, T>(args: I) -> RtResult<Action>
+        where I: IntoIterator<Item=T>,
+              T: Into<OsString> + Clone
+    {
+        let matches = app().get_matches_from_safe(args).map_err(|err| err.message)?;
+        Action::from_matches(&matches)
+    }
+
+    fn from_matches(matches: &ArgMatches) -> RtResult<Action> {
+        if let Some(clean_matches) = matches.subcommand_matches("clean") {
+            return Ok(Action::Clean(CleanConfig::from_matches(clean_matches)?));
+        }
+
+        if let Some(deps_matches) = matches.subcommand_matches("deps") {
+            return Ok(Action::Deps(Box::new(DepsConfig::from_matches(deps_matches)?)));
+        }
+
+        if let Some(completions_matches) = matches.subcommand_matches("completions") {
+            return Ok(Action::Completions(value_t_or_exit!(completions_matches.value_of("SHELL"), Shell)));
+        }
+
+        if matches.is_present("print-cache-dir") {
+            init_rusty_tags_dir(matches)?;
+            return Ok(Action::PrintPaths(vec![dirs::rusty_tags_dir()?.to_path_buf()]));
+        }
+
+        if matches.is_present("print-std-tags-path") {
+            return Ok(Action::PrintPaths(std_lib_tags_files(matches)?));
+        }
+
+        Ok(Action::UpdateTags(Box::new(Config::from_matches(matches, &[])?)))
+    }
+}
+
+/// the configuration of the 'deps' subcommand
+pub struct DepsConfig {
+    /// the configuration of the dependency resolution, the
+    /// arguments of the tags creation have their defaults
+    pub config: Config,
+
+    /// print the dependencies as json to stdout,
+    /// all other output is written to stderr
+    pub json: bool
+}
+
+impl DepsConfig {
+    fn from_matches(matches: &ArgMatches) -> RtResult<DepsConfig> {
+        let json = matches.is_present("json");
+
+        // the kinds of tags only determine the names of the tags files,
+        // so any kind is fine if none is configured
+        let mut config = Config::from_matches(matches, &[TagsKind::Vi])?;
+        config.output_to_stderr = json;
+        Ok(DepsConfig { config, json })
+    }
+}
+
+/// the configuration of the 'clean' subcommand
+pub struct CleanConfig {
+    /// start directory for the search of the 'Cargo.toml'
+    pub start_dir: PathBuf,
+
+    /// the 'Cargo.toml' of the cargo project
+    pub manifest_path: Option<PathBuf>,
+
+    /// don't ask for a confirmation before removing anything
+    pub force: bool,
+
+    /// also remove the tags files of the cargo project
+    pub project: bool,
+
+    /// the file names of the tags files of the cargo project
+    pub tags_file_names: Vec<String>,
+
+    /// don't output anything but errors
+    pub quiet: bool
+}
+
+impl CleanConfig {
+    fn from_matches(matches: &ArgMatches) -> RtResult<CleanConfig> {
+        let (start_dir, manifest_path) = start_dir_and_manifest_path(matches)?;
+        init_rusty_tags_dir(matches)?;
+        let file_config = ConfigFromFile::load()?.unwrap_or_default();
+
+        Ok(CleanConfig {
+            start_dir,
+            manifest_path,
+            force: matches.is_present("force"),
+            project: matches.is_present("project"),
+            tags_file_names: vec![file_config.vi_tags.unwrap_or(DEFAULT_VI_TAGS.to_string()),
+                                  file_config.emacs_tags.unwrap_or(DEFAULT_EMACS_TAGS.to_string()),
+                                  "GTAGS".to_string(), "GRTAGS".to_string(), "GPATH".to_string(),
+                                  "cscope.out".to_string(), "cscope.files".to_string()],
+            quiet: matches.is_present("quiet")
+        })
+    }
+}
+
+const DEFAULT_VI_TAGS: &str = "rusty-tags.vi";
+const DEFAULT_EMACS_TAGS: &str = "rusty-tags.emacs";
+
+/// the files and directories excluded from tagging, if not disabled by '--no-default-excludes'
+const DEFAULT_EXCLUDES: &[&str] = &["target"];
+
+/// the command line interface of rusty-tags
+fn app() -> App<'static, 'static> {
+    App::new("rusty-tags")
+        .about("Create ctags/etags for a cargo project and all of its dependencies")
+        // Pull version from Cargo.toml
+        .version(crate_version!())
+        .author("Daniel Trstenjak <daniel.trstenjak@gmail.com>")
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .setting(AppSettings::ArgsNegateSubcommands)
+        .arg(Arg::from_usage("[TAGS_KIND]... 'The kinds of the created tags (vi, emacs, gtags, cscope), also accepted are vim and ctags for vi and etags for emacs (default: the kinds of the .rusty-tags.toml)'")
+             .possible_values(&TagsKind::names())
+             .case_insensitive(true))
+        .arg(Arg::from_usage("-s --start-dir [DIR] 'Start directory for the search of the Cargo.toml (default: current working directory)'").global(true))
+        .arg(Arg::from_usage("--manifest-path [FILE] 'Path to the Cargo.toml of the cargo project, instead of searching it'")
+             .conflicts_with("start-dir")
+             .global(true))
+        .arg(Arg::from_usage("--stop-at-vcs-root 'Do not search the Cargo.toml above the root of the git, mercurial or subversion repository'")
+             .global(true))
+        .arg_from_usage("--output-dir [DIR] 'Directory where the tags of the cargo project are written (default: beside of the Cargo.toml)'")
+        .arg_from_usage("--stdout 'Write the tags of the cargo project to stdout instead of into a file'")
+        .arg(Arg::from_usage("--summary-json 'Print a summary of the created tags as json to stdout, all other output is written to stderr'")
+             .conflicts_with("stdout"))
+        .arg_from_usage("--append 'Keep the tags in the existing tags file of the cargo project which weren't created by rusty-tags'")
+        .arg(Arg::from_usage("--extra-tags [FILE]... 'An additional tags file which is merged into the tags of the cargo project, can be given multiple times'")
+             .number_of_values(1))
+        .arg_from_usage("--relative-paths 'Write the paths of the source files relative to the directory of the tags file, or with --stdout to the current directory'")
+        .arg_from_usage("--canonicalize 'Resolve the symlinks of the source directories, so that the tags contain the real paths of the source files'")
+        .arg(Arg::from_usage("--sort [ORDER] 'How the vi tags are sorted, like the --sort option of ctags, foldcase ignores the case (default: yes)'")
+             .possible_values(TagsSort::names()))
+        .arg(Arg::from_usage("--dedup 'Write only the first of the merged vi tags with the same name, file and pattern (default)'")
+             .overrides_with("no-dedup"))
+        .arg(Arg::from_usage("--no-dedup 'Write every merged vi tag, even if another tag has the same name, file and pattern'")
+             .overrides_with("dedup"))
+        .arg_from_usage("--tags-file-name [NAME] 'The file name of the tags file of the cargo project, e.g. .tags, only for a single kind of vi or emacs tags'")
+        .arg_from_usage("--workspace-tags 'Write the tags of all workspace members into a single tags file at the workspace root'")
+        .arg(Arg::from_usage("--single-file [PATH] 'Write the tags of the cargo project and of all dependencies only into this file, without any tags files in the source directories'")
+             .conflicts_with_all(&["stdout", "tags-file-name", "projects"]))
+        .arg(Arg::from_usage("--no-merge 'Do not merge any tags, but print the paths of the separate tags files of the cargo project, its dependencies and the standard library'")
+             .conflicts_with_all(&["stdout", "workspace-tags", "single-file", "post-hook"]))
+        .arg(Arg::from_usage("--cache-dir [DIR] 'Directory where rusty-tags puts its cache and config (default: $RUSTY_TAGS_DIR or ~/.rusty-tags)'").global(true))
+        .arg_from_usage("--print-cache-dir 'Print the directory where rusty-tags puts its cache and config, without creating any tags'")
+        .arg(Arg::from_usage("--print-std-tags-path 'Print the paths of the tags files of the Rust standard library, for the given kinds or for vi and emacs, without creating any tags'")
+             .conflicts_with("print-cache-dir"))
+        .arg_from_usage("--ctags-exe [EXE] 'The name or path of the ctags executable (default: $RUSTY_TAGS_CTAGS)'")
+        .arg_from_usage("--etags-exe [EXE] 'The name or path of the ctags executable for emacs tags (default: $RUSTY_TAGS_ETAGS or the ctags executable)'")
+        .arg_from_usage("--gtags-exe [EXE] 'The name or path of the gtags executable of GNU Global (default: $RUSTY_TAGS_GTAGS or gtags)'")
+        .arg_from_usage("--cscope-exe [EXE] 'The name or path of the cscope executable (default: $RUSTY_TAGS_CSCOPE or cscope)'")
+        .arg(Arg::from_usage("--ctags-option [OPTION]... 'An additional option for ctags, can be given multiple times, e.g. --ctags-option=--fields=+n'")
+             .number_of_values(1)
+             .allow_hyphen_values(true))
+        .arg(Arg::from_usage("--vi-ctags-option [OPTION]... 'An additional option for ctags only for vi tags, given after the ones of --ctags-option, can be given multiple times'")
+             .number_of_values(1)
+             .allow_hyphen_values(true))
+        .arg(Arg::from_usage("--etags-option [OPTION]... 'An additional option for ctags only for emacs tags, given after the ones of --ctags-option, can be given multiple times'")
+             .number_of_values(1)
+             .allow_hyphen_values(true))
+        .arg_from_usage("--rust-regex 'Give exuberant ctags definitions of Rust covering newer syntax, like async and const fns, impls and unions, ignored for universal ctags'")
+        .arg_from_usage("--all-languages 'Let ctags also tag the files of other languages than Rust, e.g. the C sources of FFI crates'")
+        .arg(Arg::from_usage("--exclude [GLOB]... 'Exclude files and directories matching the glob pattern from tagging, can be given multiple times'")
+             .number_of_values(1))
+        .arg_from_usage("--include-hidden 'Also search hidden directories for the source files given to gtags and cscope and for detecting changes, ctags already tags them'")
+        .arg_from_usage("--no-default-excludes 'Do not exclude the directory \"target\" by default'")
+        .arg_from_usage("-o --omit-deps 'Do not generate tags for dependencies'")
+        .args(&dependency_args())
+        .arg(Arg::from_usage("--dep [NAME] 'Only create the tags of this dependency of the cargo project - given by its name or by NAME@VERSION - and of its dependencies, written to NAME-<tags file> in the --output-dir or the workspace root'")
+             .conflicts_with_all(&["workspace-tags", "single-file", "projects", "watch", "std-only"]))
+        .arg_from_usage("--depth [N] 'Only generate tags for the dependencies up to N levels below the cargo project, 1 only for the direct ones (default: all)'")
+        .arg_from_usage("--examples 'Also tag the examples of the cargo project, but not the ones of its dependencies'")
+        .arg_from_usage("--benches 'Also tag the benchmarks of the cargo project, but not the ones of its dependencies'")
+        .arg_from_usage("--tests 'Also tag the integration tests of the cargo project, but not the ones of its dependencies'")
+        .arg_from_usage("--bins 'Also tag the binaries in 'src/bin' of the cargo project, but not the ones of its dependencies'")
+        .arg_from_usage("--all-crate-targets 'Like --examples --benches --tests --bins'")
+        .arg(Arg::from_usage("-f --force-recreate 'Forces the recreation of the tags of all dependencies and the Rust standard library, ignoring any cached tags and stored hashes'")
+             .visible_alias("force"))
+        .arg_from_usage("--strict 'Fail if a created tags file of the cargo project is empty or malformed, or if the --post-hook failed, instead of only warning'")
+        .arg(Arg::from_usage("--post-hook [CMD] 'A shell command run after a tags file of the cargo project was written, getting RUSTY_TAGS_FILE, RUSTY_TAGS_CRATE and RUSTY_TAGS_KIND'")
+             .conflicts_with("stdout"))
+        .arg_from_usage("--since [GITREF] 'Only recreate the tags of the cargo project and its path dependencies if git reports changed files in them since GITREF, instead of comparing the files'")
+        .arg_from_usage("--strict-hash 'Detect changed source files by their contents instead of their modification times'")
+        .arg_from_usage("--dry-run 'Only output which tags would be created and where they would be written, without creating them'")
+        .arg_from_usage("-k --keep-going 'Continue with the other sources if the tags creation of a source failed'")
+        .arg_from_usage("--fail-on-missing 'Fail if the sources of some dependencies couldn't be found, after creating the tags without them'")
+        .arg_from_usage("--timeout [SECS] 'Kill an execution of cargo or ctags which takes longer (default: no time limit)'")
+        .arg_from_usage("--artifacts-in-target 'Put the cached tags of the dependencies into the directory rusty-tags of the target directory, so that cargo clean removes them'")
+        .arg_from_usage("--lock-timeout [SECS] 'How long to wait for another rusty-tags updating the cached tags, before failing (default: 60)'")
+        .arg(Arg::from_usage("--no-lock 'Do not lock the cached tags while updating them, so that multiple rusty-tags can run at once'")
+             .conflicts_with("lock-timeout"))
+        .arg_from_usage("--fetch-retries [N] 'Retry fetching the source and metadata this often if cargo failed, e.g. because of network errors (default: 2)'")
+        .arg_from_usage("--no-std-lib 'Do not create the tags of the Rust standard library, like std_lib_tags = false in the .rusty-tags.toml'")
+        .arg(Arg::from_usage("--ignore-std-missing 'Do not hint about the missing source of the Rust standard library, also not in later runs'")
+             .conflicts_with("std-only"))
+        .arg(Arg::from_usage("--std-only 'Only recreate the tags of the Rust standard library, without touching the cargo project'")
+             .conflicts_with_all(&["stdout", "dry-run", "no-std-lib"]))
+        .arg(Arg::from_usage("--watch 'After the tags creation watch the source files of the cargo project and its path dependencies and update the tags on changes'")
+             .conflicts_with_all(&["dry-run", "stdout", "summary-json", "std-only"]))
+        .arg(Arg::from_usage("--projects [GLOB] 'Create the tags of all cargo projects in the directories matching the glob pattern, e.g. \"crates/*\"'")
+             .conflicts_with_all(&["start-dir", "manifest-path", "stop-at-vcs-root", "summary-json", "watch", "std-only"]))
+        .arg_from_usage("-v --verbose... 'Verbose output about all operations, given twice also outputs the stderr of ctags, three times also the debug log'")
+        .arg(Arg::from_usage("-q --quiet 'Don't output anything but errors and warnings'").global(true))
+        .arg_from_usage("--no-warnings 'Don't output any warnings'")
+        .arg(Arg::from_usage("--color [WHEN] 'Color the status output, auto colors it on a terminal if NO_COLOR isn't set (default: auto)'")
+             .possible_values(ColorChoice::names()))
+        .arg_from_usage("-j --jobs [N] 'Num jobs used for the tags creation, 0 uses the num available cpus (default: num available cpus)'")
+        .arg(Arg::from_usage("-n --num-threads [NUM] 'The old name of --jobs'")
+             .conflicts_with("jobs")
+             .hidden(true))
+        .subcommand(SubCommand::with_name("clean")
+            .about("Removes the cached tags of the dependencies")
+            .arg_from_usage("--force 'Don't ask for a confirmation'")
+            .arg_from_usage("--project 'Also remove the tags files of the cargo project'"))
+        .subcommand(SubCommand::with_name("deps")
+            .about("Prints the resolved dependencies of the cargo project, without creating any tags")
+            .arg_from_usage("--json 'Print the dependencies as json'")
+            .arg_from_usage("-v --verbose 'Verbose output about the resolution of the dependencies'")
+            .args(&dependency_args()))
+        .subcommand(SubCommand::with_name("completions")
+            .about("Prints the completion script for a shell")
+            .arg(Arg::from_usage("<SHELL> 'The shell of the completion script'")
+                 .possible_values(&Shell::variants())))
+}
+
+/// The arguments selecting the dependencies of the cargo project, which are
+/// shared by the tags creation and the 'deps' subcommand.
+fn dependency_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::from_usage("--no-fetch 'Do not fetch the sources of the dependencies, only use the already downloaded ones'"),
+        Arg::from_usage("--dev-deps 'Also generate tags for the dev dependencies'"),
+        Arg::from_usage("--build-deps 'Also generate tags for the build dependencies'"),
+        Arg::from_usage("--exclude-dep [NAME]... 'Do not generate tags for the dependency, given by its name or by NAME@VERSION, can be given multiple times'")
+            .number_of_values(1),
+        Arg::from_usage("--only-dep [NAME]... 'Only generate tags for the dependency, given by its name or by NAME@VERSION, can be given multiple times'")
+            .number_of_values(1)
+            .conflicts_with("exclude-dep"),
+        Arg::from_usage("--crates-from [FILE] 'Only generate tags for the crates listed in the file - or with - in stdin - one NAME or NAME@VERSION per line, even for indirect dependencies'")
+            .conflicts_with_all(&["exclude-dep", "only-dep"]),
+        Arg::from_usage("--features [FEATURES]... 'Space or comma separated list of features to activate, like for cargo'")
+            .number_of_values(1),
+        Arg::from_usage("--no-default-features 'Do not activate the `default` feature'"),
+        Arg::from_usage("--all-features 'Activate all available features'")
+            .conflicts_with_all(&["features", "no-default-features"]),
+        Arg::from_usage("--target [TRIPLE] 'Only include the target specific dependencies of this target (default: the host target)'"),
+        Arg::from_usage("--all-targets 'Include the target specific dependencies of all targets'")
+            .conflicts_with("target")
+    ]
+}
+
+/// Writes the completion script of 'shell' for the command line interface to stdout.
+pub fn print_completions(shell: Shell) {
+    app().gen_completions_to("rusty-tags", shell, &mut io::stdout());
+}
+
+/// Checks the 'name' given to '--tags-file-name', which has to be a plain file
+/// name and can only be used for a single kind of vi or emacs tags.
+fn checked_tags_file_name(name: &str, tags_specs: &[TagsSpec]) -> RtResult<String> {
+    if name.is_empty() || name == "." || name == ".." || name.contains('/') || name.contains(std::path::MAIN_SEPARATOR) {
+        return Err(format!("Invalid name given to '--tags-file-name': '{}', expected a file name without any directories!", name).into());
+    }
+
+    match tags_specs {
+        [tags_spec] if ! tags_spec.kind.is_database() => Ok(name.to_string()),
+        [tags_spec] => Err(format!("'--tags-file-name' can't be used for {}, which uses fixed file names!", tags_spec.kind).into()),
+        _           => Err("'--tags-file-name' can only be used for a single kind of tags!".into())
+    }
+}
+
+/// Reads the crates of '--crates-from' from 'file', or from stdin for '-'. Every
+/// non-empty line is a crate, lines starting with '#' are comments.
+fn read_crates(file: &str) -> RtResult<Vec<String>> {
+    let mut contents = String::new();
+    if file == "-" {
+        io::stdin().read_to_string(&mut contents)
+            .map_err(|err| format!("Couldn't read the crates of '--crates-from' from stdin: {}", err))?;
+    } else {
+        File::open(file)
+            .and_then(|mut f| f.read_to_string(&mut contents))
+            .map_err(|err| format!("Couldn't read the crates of '--crates-from' from '{}': {}", file, err))?;
+    }
+
+    let crates: Vec<_> = contents.lines()
+        .map(str::trim)
+        .filter(|l| ! l.is_empty() && ! l.starts_with('#'))
+        .map(str::to_string)
+        .collect();
+
+    if crates.is_empty() {
+        return Err(format!("No crates given by '--crates-from {}'!", file).into());
+    }
+
+    Ok(crates)
+}
+
+/// The directories of the cargo projects matching the glob 'pattern' of '--projects',
+/// which might match the directories or their 'Cargo.toml' files.
+fn project_dirs(pattern: &str) -> RtResult<Vec<PathBuf>> {
+    let paths = glob::glob(pattern)
+        .map_err(|err| format!("Invalid glob pattern given to '--projects': '{}': {}", pattern, err))?;
+
+    let current_dir = env::current_dir()?;
+    let mut dirs = Vec::new();
+    for path in paths {
+        let path = current_dir.join(path.map_err(|err| format!("Couldn't read '{}' of '--projects': {}", err.path().display(), err.error()))?);
+        if path.join("Cargo.toml").is_file() {
+            dirs.push(path);
+        } else if path.is_file() && path.file_name().is_some_and(|name| name == "Cargo.toml") {
+            dirs.extend(path.parent().map(Path::to_path_buf));
+        }
+    }
+
+    dirs.sort();
+    dirs.dedup();
+    if dirs.is_empty() {
+        return Err(format!("No cargo projects found by '--projects {}'!", pattern).into());
+    }
+
+    Ok(dirs)
+}
+
+/// Reads the '--start-dir' argument, the current working directory by default.
+fn start_dir(matches: &ArgMatches) -> RtResult<PathBuf> {
+    let start_dir = matches.value_of("start-dir")
+        .map(PathBuf::from)
+        .unwrap_or(env::current_dir()?);
+
+    if ! start_dir.is_dir() {
+        return Err(format!("Invalid directory given to '--start-dir': '{}'!", start_dir.display()).into());
+    }
+
+    Ok(start_dir)
+}
+
+/// Reads the '--manifest-path' argument, then the start directory is the directory
+/// of the 'Cargo.toml'. With '--stop-at-vcs-root' the 'Cargo.toml' is searched
+/// from the start directory without leaving the version control repository.
+fn start_dir_and_manifest_path(matches: &ArgMatches) -> RtResult<(PathBuf, Option<PathBuf>)> {
+    if let Some(path) = matches.value_of("manifest-path") {
+        let path = env::current_dir()?.join(path);
+        if ! path.exists() {
+            return Err(format!("The file given to '--manifest-path' doesn't exist: '{}'!", path.display()).into());
+        }
+
+        if ! path.is_file() || path.file_name().is_none_or(|name| name != "Cargo.toml") {
+            return Err(format!("The path given to '--manifest-path' isn't a 'Cargo.toml' file: '{}'!", path.display()).into());
+        }
+
+        let start_dir = path.parent().map(Path::to_path_buf).unwrap_or(env::current_dir()?);
+        return Ok((start_dir, Some(path)));
+    }
+
+    let start_dir = start_dir(matches)?;
+    if matches.is_present("stop-at-vcs-root") {
+        let manifest_path = find_cargo_toml_inside_of_repo(&env::current_dir()?.join(&start_dir))?;
+        return Ok((start_dir, Some(manifest_path)));
+    }
+
+    Ok((start_dir, None))
+}
+
+/// the directories marking the root of a version control repository
+const VCS_DIRS: &[&str] = &[".git", ".hg", ".svn"];
+
+/// The files changed since the git ref 'git_ref' of '--since', in the git repository
+/// containing 'dir': the added, modified and deleted files reported by 'git diff'
+/// and the untracked files, which aren't ignored.
+fn files_changed_since(dir: &Path, git_ref: &str) -> RtResult<Vec<PathBuf>> {
+    let git = |dir: &Path, args: &[&str]| -> RtResult<String> {
+        let output = Command::new("git").args(args).current_dir(dir).output()
+            .map_err(|err| format!("Couldn't execute 'git' for '--since': {}", err))?;
+
+        if ! output.status.success() {
+            return Err(RtErr::CommandFailed {
+                command: format!("git {}", args.join(" ")),
+                target: format!("the directory '{}'", dir.display()),
+                status: output.status,
+                stderr: String::from_utf8_lossy(&output.stderr).into_owned()
+            });
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    };
+
+    // the paths of both commands are relative to the root of the repository
+    let repo_root = PathBuf::from(git(dir, &["rev-parse", "--show-toplevel"])?.trim_end());
+    let mut files = Vec::new();
+    for args in &[&["diff", "--name-only", "-z", git_ref, "--"][..], &["ls-files", "-z", "--others", "--exclude-standard"][..]] {
+        files.extend(git(&repo_root, args)?
+            .split('\0')
+            .filter(|f| ! f.is_empty())
+            .map(|f| repo_root.join(f)));
+    }
+
+    debug!("Files changed since '{}': {:?}", git_ref, files);
+    Ok(files)
+}
+
+/// Searches the 'Cargo.toml' upwards from 'start_dir' and stops at the
+/// root of the version control repository.
+fn find_cargo_toml_inside_of_repo(start_dir: &Path) -> RtResult<PathBuf> {
+    let mut dir = start_dir.to_path_buf();
+    loop {
+        let cargo_toml = dir.join("Cargo.toml");
+        debug!("Searching '{}'", cargo_toml.display());
+        if cargo_toml.is_file() {
+            return Ok(cargo_toml);
+        }
+
+        if VCS_DIRS.iter().any(|vcs_dir| dir.join(vcs_dir).exists()) {
+            return Err(format!("Couldn't find 'Cargo.toml' starting at directory '{}' inside of the repository at '{}'!",
+                               start_dir.display(), dir.display()).into());
+        }
+
+        if ! dir.pop() {
+            return Err(format!("Couldn't find 'Cargo.toml' starting at directory '{}' and no repository root was found!",
+                               start_dir.display()).into());
+        }
+    }
+}
+
+/// The tags files of the standard library in the rusty-tags directory for the kinds
+/// of 'matches' - by default for vi and emacs - which don't have to exist yet.
+fn std_lib_tags_files(matches: &ArgMatches) -> RtResult<Vec<PathBuf>> {
+    init_rusty_tags_dir(matches)?;
+    let kinds = if matches.is_present("TAGS_KIND") {
+        values_t_or_exit!(matches.values_of("TAGS_KIND"), TagsKind)
+    } else {
+        vec![TagsKind::Vi, TagsKind::Emacs]
+    };
+
+    let mut files = Vec::new();
+    for kind in kinds {
+        if kind.is_database() {
+            continue;
+        }
+
+        let file = dirs::rust_std_lib_tags_file(kind.file_extension())?;
+        if ! files.contains(&file) {
+            files.push(file);
+        }
+    }
+
+    Ok(files)
+}
+
+/// Applies the '--cache-dir' argument and ensures that the rusty-tags directory is usable.
+fn init_rusty_tags_dir(matches: &ArgMatches) -> RtResult<()> {
+    if let Some(dir) = matches.value_of("cache-dir") {
+        dirs::set_rusty_tags_dir(env::current_dir()?.join(dir));
+    }
+
+    dirs::check_rusty_tags_dir_writable()
 }
 
 impl Config {
-   pub fn from_command_args() -> RtResult<Config> {
-       let matches = App::new("rusty-tags")
-           .about("Create ctags/etags for a cargo project and all of its dependencies")
-           // Pull version from Cargo.toml
-           .version(crate_version!())
-           .author("Daniel Trstenjak <daniel.trstenjak@gmail.com>")
-           .arg_from_usage("<TAGS_KIND> 'The kind of the created tags (vi, emacs)'")
-           .arg_from_usage("-s --start-dir [DIR] 'Start directory for the search of the Cargo.toml (default: current working directory)'")
-           .arg_from_usage("-o --omit-deps 'Do not generate tags for dependencies'")
-           .arg_from_usage("-f --force-recreate 'Forces the recreation of the tags of all dependencies and the Rust standard library'")
-           .arg_from_usage("-v --verbose 'Verbose output about all operations'")
-           .arg_from_usage("-q --quiet 'Don't output anything but errors'")
-           .arg_from_usage("-n --num-threads [NUM] 'Num threads used for the tags creation (default: num available physical cpus)'")
-           .get_matches();
-
-       let start_dir = matches.value_of("start-dir")
-           .map(PathBuf::from)
-           .unwrap_or(env::current_dir()?);
-
-       if ! start_dir.is_dir() {
-           return Err(format!("Invalid directory given to '--start-dir': '{}'!", start_dir.display()).into());
+   /// The configuration of 'matches', with 'default_kinds' if neither the
+   /// arguments nor the '.rusty-tags.toml' specify the kinds of tags.
+   fn from_matches(matches: &ArgMatches, default_kinds: &[TagsKind]) -> RtResult<Config> {
+       // stdin can only be read once, so the crates are shared by all projects
+       let crates = match matches.value_of("crates-from") {
+           Some(file) => Some(read_crates(file)?),
+           None       => None
+       };
+
+       let mut config = Config::from_matches_for_project(matches, default_kinds, None, crates.clone())?;
+       if let Some(pattern) = matches.value_of("projects") {
+           for dir in project_dirs(pattern)? {
+               config.projects.push(Config::from_matches_for_project(matches, default_kinds, Some(&dir), crates.clone())?);
+           }
+       }
+
+       Ok(config)
+   }
+
+   /// The configuration of 'matches' for the cargo project in 'project_dir',
+   /// by default the one found by '--start-dir' or '--manifest-path'.
+   fn from_matches_for_project(matches: &ArgMatches,
+                               default_kinds: &[TagsKind],
+                               project_dir: Option<&Path>,
+                               crates: Option<Vec<String>>)
+                               -> RtResult<Config> {
+       let (start_dir, manifest_path) = match project_dir {
+           Some(dir) => (dir.to_path_buf(), Some(dir.join("Cargo.toml"))),
+           None      => start_dir_and_manifest_path(matches)?
+       };
+
+       let project_config = ProjectConfig::load(&env::current_dir()?.join(&start_dir))?.unwrap_or_default();
+
+       // the current working directory is later changed to 'start_dir',
+       // so a relative 'output_dir' has to be made absolute beforehand
+       let output_dir = match matches.value_of("output-dir") {
+           Some(dir) => Some(env::current_dir()?.join(dir)),
+           None      => project_config.output_dir.clone()
+       };
+
+       let extra_tags_files: Vec<PathBuf> = match matches.values_of("extra-tags") {
+           Some(files) => {
+               let current_dir = env::current_dir()?;
+               files.map(|f| current_dir.join(f)).collect()
+           }
+
+           None => Vec::new()
+       };
+
+       let dry_run = matches.is_present("dry-run");
+       let keep_going = matches.is_present("keep-going");
+       if let Some(ref dir) = output_dir {
+           if ! dir.is_dir() && ! dry_run {
+               fs::create_dir_all(dir)
+                   .map_err(|err| format!("Couldn't create directory given to '--output-dir': '{}': {}", dir.display(), err))?;
+           }
        }
 
+       init_rusty_tags_dir(matches)?;
 
        let (vi_tags, emacs_tags, ctags_exe, ctags_options) = {
-           let mut vt = "rusty-tags.vi".to_string();
-           let mut et = "rusty-tags.emacs".to_string();
+           let mut vt = DEFAULT_VI_TAGS.to_string();
+           let mut et = DEFAULT_EMACS_TAGS.to_string();
            let mut cte = None;
            let mut cto = "".to_string();
            if let Some(file_config) = ConfigFromFile::load()? {
@@ -75,41 +788,319 @@ impl Config {
            (vt, et, cte, cto)
        };
 
-       let kind = value_t_or_exit!(matches.value_of("TAGS_KIND"), TagsKind);
+       let kinds = {
+           let mut kinds = if matches.is_present("TAGS_KIND") {
+               values_t_or_exit!(matches.values_of("TAGS_KIND"), TagsKind)
+           } else {
+               project_config.kinds()?
+           };
+
+           if kinds.is_empty() {
+               kinds = default_kinds.to_vec();
+           }
+
+           if kinds.is_empty() {
+               return Err("Missing the kinds of the created tags, e.g. 'rusty-tags vi', or 'kinds' in the '.rusty-tags.toml'!".into());
+           }
+
+           let mut unique_kinds = Vec::new();
+           for kind in kinds {
+               if ! unique_kinds.contains(&kind) {
+                   unique_kinds.push(kind);
+               }
+           }
+
+           unique_kinds
+       };
+       let tags_to_stdout = matches.is_present("stdout");
+       let summary_json = matches.is_present("summary-json");
+       let no_merge = matches.is_present("no-merge");
+       let output_to_stderr = tags_to_stdout || summary_json || no_merge;
+       let single_file = match matches.value_of("single-file") {
+           Some(file) => Some(env::current_dir()?.join(file)),
+           None       => None
+       };
+
+       let workspace_tags = matches.is_present("workspace-tags") || single_file.is_some();
+       let append = matches.is_present("append");
+       let no_fetch = matches.is_present("no-fetch");
        let omit_deps = matches.is_present("omit-deps");
+       let dev_deps = matches.is_present("dev-deps");
+       let build_deps = matches.is_present("build-deps");
+       let exclude_deps: Vec<String> = matches.values_of("exclude-dep")
+           .map(|deps| deps.map(str::to_string).collect())
+           .unwrap_or_default();
+
+       let only_deps: Vec<String> = matches.values_of("only-dep")
+           .map(|deps| deps.map(str::to_string).collect())
+           .unwrap_or_default();
+
+       let dep = matches.value_of("dep").map(str::to_string);
+
+       let changed_since = match matches.value_of("since") {
+           Some(git_ref) => Some(files_changed_since(&env::current_dir()?.join(&start_dir), git_ref)?),
+           None          => None
+       };
+
+       let depth = match matches.value_of("depth") {
+           Some(depth) => Some(depth.parse::<usize>()
+               .map_err(|_| format!("Invalid value given to '--depth': '{}', expected a number >= 0!", depth))?),
+
+           None => None
+       };
+
+       let features: Vec<String> = matches.values_of("features")
+           .map(|fs| fs.flat_map(|f| f.split([',', ' ']))
+                       .filter(|f| ! f.is_empty())
+                       .map(str::to_string)
+                       .collect())
+           .unwrap_or_default();
+
+       let no_default_features = matches.is_present("no-default-features");
+       let all_features = matches.is_present("all-features");
+       let target = matches.value_of("target").map(str::to_string);
+       let all_targets = matches.is_present("all-targets");
+       let local_target_dirs = [("bins", "src/bin"), ("examples", "examples"), ("benches", "benches"), ("tests", "tests")]
+           .iter()
+           .filter(|&&(arg, _)| matches.is_present(arg) || matches.is_present("all-crate-targets"))
+           .map(|&(_, dir)| dir)
+           .collect();
+
        let force_recreate = matches.is_present("force-recreate");
+       let strict_hash = matches.is_present("strict-hash");
        let quiet = matches.is_present("quiet");
-       let verbose = if quiet { false } else { matches.is_present("verbose") };
+       let no_warnings = matches.is_present("no-warnings");
+       let colored = if matches.is_present("color") {
+           value_t_or_exit!(matches.value_of("color"), ColorChoice)
+       } else {
+           ColorChoice::Auto
+       }.colors_stderr();
+       let verbosity = if quiet { 0 } else { matches.occurrences_of("verbose") };
+       let verbose = verbosity > 0;
 
        let num_threads = if verbose {
-           println!("Switching to single threaded for verbose output");
+           output!(output_to_stderr, "Switching to single threaded for verbose output");
            1
        } else {
-           value_t!(matches.value_of("num-threads"), u32)
-               .map(|n| max(1, n))
-               .unwrap_or(num_cpus::get_physical() as u32)
+           let jobs = match matches.value_of("jobs").or_else(|| matches.value_of("num-threads")) {
+               Some(jobs) => Some(jobs.parse::<u32>()
+                   .map_err(|_| format!("Invalid value given to '--jobs': '{}', expected a number >= 1 or 0 for the num available cpus!", jobs))?),
+               None => project_config.jobs
+           };
+
+           match jobs {
+               Some(0) | None => max(1, num_cpus::get() as u32),
+               Some(jobs)     => jobs
+           }
        };
 
        if verbose {
-           println!("Using configuration: vi_tags='{}', emacs_tags='{}', ctags_exe='{:?}', ctags_options='{}'",
+           output!(output_to_stderr, "Using configuration: vi_tags='{}', emacs_tags='{}', ctags_exe='{:?}', ctags_options='{}'",
                     vi_tags, emacs_tags, ctags_exe, ctags_options);
        }
 
-       let ctags_exe = detect_tags_exe(&ctags_exe)?;
-       if verbose {
-           println!("Found ctags executable: {:?}", ctags_exe);
+       let timeout = match matches.value_of("timeout") {
+           Some(secs) => match secs.parse::<u64>() {
+               Ok(secs) if secs > 0 => Some(Duration::from_secs(secs)),
+               _ => return Err(format!("Invalid value given to '--timeout': '{}', expected a number of seconds >= 1!", secs).into())
+           },
+
+           None => None
+       };
+
+       let fetch_retries = match matches.value_of("fetch-retries") {
+           Some(retries) => retries.parse::<u32>()
+               .map_err(|_| format!("Invalid value given to '--fetch-retries': '{}', expected a number >= 0!", retries))?,
+
+           None => 2
+       };
+
+       let lock_timeout = match matches.value_of("lock-timeout") {
+           Some(secs) => secs.parse::<u64>()
+               .map(Duration::from_secs)
+               .map_err(|_| format!("Invalid value given to '--lock-timeout': '{}', expected a number of seconds >= 0!", secs))?,
+
+           None => Duration::from_secs(60)
+       };
+
+       let extra_ctags_options: Vec<String> = matches.values_of("ctags-option")
+           .map(|opts| opts.map(str::to_string).collect())
+           .or_else(|| project_config.ctags_options.clone())
+           .unwrap_or_default();
+
+       let excludes: Vec<String> = {
+           let mut excludes = Vec::new();
+           if ! matches.is_present("no-default-excludes") {
+               excludes.extend(DEFAULT_EXCLUDES.iter().map(|e| e.to_string()));
+           }
+
+           if let Some(patterns) = matches.values_of("exclude") {
+               excludes.extend(patterns.map(str::to_string));
+           } else if let Some(ref patterns) = project_config.excludes {
+               excludes.extend(patterns.iter().cloned());
+           }
+
+           excludes
+       };
+
+       let tags_sort = if matches.is_present("sort") {
+           value_t_or_exit!(matches.value_of("sort"), TagsSort)
+       } else {
+           TagsSort::Yes
+       };
+
+       let rust_regex = matches.is_present("rust-regex");
+       let all_languages = matches.is_present("all-languages");
+       let tags_specs = {
+           let mut specs = Vec::with_capacity(kinds.len());
+           for kind in kinds {
+               // the databases of GNU Global and cscope are never merged with other tags
+               if ! kind.is_database() {
+                   for file in &extra_tags_files {
+                       check_tags_file(file, kind)?;
+                   }
+               }
+
+               let (exe, exe_version) = detect_tags_exe(&tags_exe_name(matches, kind, &ctags_exe), kind, verbose, output_to_stderr)?;
+               if verbose {
+                   output!(output_to_stderr, "Found tags executable for {} tags: {:?}", kind, exe);
+               }
+
+               // the default sorting of ctags isn't given, so that the ctags command
+               // and therefore the cached tags stay the same as without '--sort'
+               let mut extra_ctags_options = extra_ctags_options.clone();
+               if kind == TagsKind::Vi && tags_sort != TagsSort::Yes {
+                   extra_ctags_options.insert(0, tags_sort.ctags_option().to_string());
+               }
+
+               // the options of the kind are given last, so they can override the ones for all kinds
+               match kind {
+                   TagsKind::Vi    => extra_ctags_options.extend(kind_ctags_options(matches, "vi-ctags-option", project_config.vi.as_ref())),
+                   TagsKind::Emacs => extra_ctags_options.extend(kind_ctags_options(matches, "etags-option", project_config.emacs.as_ref())),
+                   _               => {}
+               }
+
+               let rust_regex_file = match exe {
+                   TagsExe::ExuberantCtags(_) if rust_regex => Some(dirs::rust_regex_file()?),
+                   TagsExe::UniversalCtags(_) if rust_regex && verbose => {
+                       output!(output_to_stderr, "Ignoring '--rust-regex' for {} tags, universal ctags parses Rust by itself", kind);
+                       None
+                   }
+
+                   _ => None
+               };
+
+               let mut spec = TagsSpec::new(kind, exe, vi_tags.clone(), emacs_tags.clone(), ctags_options.clone(), extra_ctags_options, excludes.clone())?;
+               if let Some(file) = rust_regex_file {
+                   spec.set_rust_regex_file(file);
+               }
+
+               if all_languages {
+                   spec.set_all_languages();
+               }
+
+               if let Some(version) = exe_version {
+                   spec.set_exe_version(version);
+               }
+
+               specs.push(spec);
+           }
+
+           specs
+       };
+
+       let tags_file_name = match matches.value_of("tags-file-name") {
+           Some(name) => Some(checked_tags_file_name(name, &tags_specs)?),
+           None       => None
+       };
+
+       if single_file.is_some() {
+           match tags_specs.as_slice() {
+               [tags_spec] if ! tags_spec.kind.is_database() => {}
+               _ => return Err("'--single-file' can only be used for a single kind of vi or emacs tags!".into())
+           }
        }
 
        Ok(Config {
-           tags_spec: TagsSpec::new(kind, ctags_exe, vi_tags, emacs_tags, ctags_options)?,
-           start_dir: start_dir,
-           omit_deps: omit_deps,
-           force_recreate: force_recreate,
-           verbose: verbose,
-           quiet: quiet,
-           num_threads: num_threads
+           tags_spec: tags_specs[0].clone(),
+           tags_specs,
+           start_dir,
+           manifest_path,
+           output_dir,
+           tags_to_stdout,
+           summary_json,
+           output_to_stderr,
+           workspace_tags,
+           single_file,
+           no_merge,
+           no_fetch,
+           omit_deps,
+           dev_deps,
+           build_deps,
+           exclude_deps,
+           only_deps,
+           crates,
+           dep,
+           changed_since,
+           depth,
+           features,
+           no_default_features,
+           all_features,
+           target,
+           all_targets,
+           local_target_dirs,
+           tags_file_name,
+           force_recreate,
+           strict_hash,
+           append,
+           extra_tags_files,
+           relative_paths: matches.is_present("relative-paths"),
+           canonicalize: matches.is_present("canonicalize"),
+           include_hidden: matches.is_present("include-hidden"),
+           dedup_tags: ! matches.is_present("no-dedup"),
+           tags_sort,
+           strict: matches.is_present("strict"),
+           post_hook: matches.value_of("post-hook").map(str::to_string),
+           dry_run,
+           keep_going,
+           fail_on_missing: matches.is_present("fail-on-missing"),
+           verbose,
+           verbosity,
+           quiet,
+           colored,
+           no_warnings,
+           num_threads,
+           timeout,
+           fetch_retries,
+           no_lock: matches.is_present("no-lock"),
+           artifacts_in_target: matches.is_present("artifacts-in-target"),
+           artifacts_dir: None,
+           lock_timeout,
+           watch: matches.is_present("watch"),
+           std_lib_tags: ! matches.is_present("no-std-lib") && project_config.std_lib_tags.unwrap_or(true),
+           ignore_std_missing: matches.is_present("ignore-std-missing"),
+           std_only: matches.is_present("std-only"),
+           projects: Vec::new()
        })
    }
+
+   /// The directory of the cached tags of the sources.
+   pub fn artifacts_dir(&self) -> RtResult<PathBuf> {
+       match self.artifacts_dir {
+           Some(ref dir) => Ok(dir.clone()),
+           None          => Ok(dirs::rusty_tags_cache_dir()?.to_path_buf())
+       }
+   }
+
+   /// The file name of the tags files of the roots.
+   pub fn root_tags_file_name(&self) -> &str {
+       self.tags_file_name.as_deref().unwrap_or(self.tags_spec.file_name())
+   }
+
+   /// Returns the configuration for creating the tags of 'tags_spec'.
+   pub fn for_tags_spec(&self, tags_spec: &TagsSpec) -> Config {
+       Config { tags_spec: tags_spec.clone(), ..self.clone() }
+   }
 }
 
 /// Represents the data from a `.rusty-tags/config.toml` configuration file.
@@ -136,14 +1127,101 @@ impl ConfigFromFile {
         }
 
         let config = map_file(&config_file, |contents| {
-            let config = toml::from_str(&contents)?;
-            Ok(config)
+            toml::from_str(&contents)
+                .map_err(|error| RtErr::TomlParse { file: Some(config_file.clone()), error })
         })?;
 
         Ok(Some(config))
     }
 }
 
+/// the file name of the configuration of a cargo project
+const PROJECT_CONFIG_FILE_NAME: &str = ".rusty-tags.toml";
+
+/// Represents the data from a `.rusty-tags.toml` file of a cargo project, which
+/// sets the defaults for the command line arguments given for each run.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct ProjectConfig {
+    /// the kinds of the created tags, if none are given on the command line
+    kinds: Option<Vec<String>>,
+
+    /// glob patterns of the excluded files and directories, like '--exclude'
+    excludes: Option<Vec<String>>,
+
+    /// additional options given to ctags, like '--ctags-option'
+    ctags_options: Option<Vec<String>>,
+
+    /// num threads used for the tags creation, like '--jobs'
+    jobs: Option<u32>,
+
+    /// directory where the tags files of the cargo project roots are
+    /// written, like '--output-dir', relative to the directory of the file
+    output_dir: Option<PathBuf>,
+
+    /// if the tags of the rust standard library are created
+    std_lib_tags: Option<bool>,
+
+    /// the configuration only for vi tags, the table '[vi]'
+    vi: Option<KindProjectConfig>,
+
+    /// the configuration only for emacs tags, the table '[emacs]'
+    emacs: Option<KindProjectConfig>
+}
+
+/// The configuration of a '.rusty-tags.toml' for a single kind of tags.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct KindProjectConfig {
+    /// additional options given to ctags for this kind, after the ones of 'ctags_options',
+    /// like '--vi-ctags-option' or '--etags-option'
+    #[serde(alias = "etags_options")]
+    ctags_options: Option<Vec<String>>
+}
+
+/// The additional ctags options of a single kind of tags, given by the argument 'arg'
+/// or else by 'kind_config' of the '.rusty-tags.toml'.
+fn kind_ctags_options(matches: &ArgMatches, arg: &str, kind_config: Option<&KindProjectConfig>) -> Vec<String> {
+    matches.values_of(arg)
+        .map(|opts| opts.map(str::to_string).collect())
+        .or_else(|| kind_config.and_then(|c| c.ctags_options.clone()))
+        .unwrap_or_default()
+}
+
+impl ProjectConfig {
+    /// Searches the '.rusty-tags.toml' upwards from 'start_dir', without
+    /// leaving the version control repository.
+    fn load(start_dir: &Path) -> RtResult<Option<ProjectConfig>> {
+        let mut dir = start_dir.to_path_buf();
+        loop {
+            let config_file = dir.join(PROJECT_CONFIG_FILE_NAME);
+            if config_file.is_file() {
+                let mut config: ProjectConfig = map_file(&config_file, |contents| {
+                    toml::from_str(&contents)
+                        .map_err(|error| RtErr::TomlParse { file: Some(config_file.clone()), error })
+                })?;
+
+                config.output_dir = config.output_dir.map(|d| dir.join(d));
+                return Ok(Some(config));
+            }
+
+            if VCS_DIRS.iter().any(|vcs_dir| dir.join(vcs_dir).exists()) || ! dir.pop() {
+                return Ok(None);
+            }
+        }
+    }
+
+    fn kinds(&self) -> RtResult<Vec<TagsKind>> {
+        let mut kinds = Vec::new();
+        for kind in self.kinds.iter().flatten() {
+            kinds.push(kind.parse::<TagsKind>()
+                .map_err(|err| format!("{} in '{}'", err, PROJECT_CONFIG_FILE_NAME))?);
+        }
+
+        Ok(kinds)
+    }
+}
+
 /// Reads `file` into a string which is passed to the function `f`
 /// and its return value is returned by `map_file`.
 fn map_file<R, F>(file: &Path, f: F) -> RtResult<R>
@@ -158,13 +1236,45 @@ fn map_file<R, F>(file: &Path, f: F) -> RtResult<R>
     Ok(r)
 }
 
-fn detect_tags_exe(ctags_exe: &Option<String>) -> RtResult<TagsExe> {
-    let exes = if let &Some(ref exe) = ctags_exe {
+/// The ctags executable for the tags of 'kind' in the order of precedence: for vi tags
+/// '--ctags-exe', 'RUSTY_TAGS_CTAGS', for emacs tags '--etags-exe', 'RUSTY_TAGS_ETAGS' and
+/// then for both '--ctags-exe', 'RUSTY_TAGS_CTAGS' and 'ctags_exe' of the config file.
+/// For gtags only '--gtags-exe' and 'RUSTY_TAGS_GTAGS' are considered, for cscope
+/// only '--cscope-exe' and 'RUSTY_TAGS_CSCOPE'.
+fn tags_exe_name(matches: &ArgMatches, kind: TagsKind, file_config_exe: &Option<String>) -> Option<String> {
+    let from_args = |arg, env_var| {
+        matches.value_of(arg)
+            .map(str::to_string)
+            .or_else(|| env::var(env_var).ok().filter(|e| ! e.is_empty()))
+    };
+
+    let exe = match kind {
+        TagsKind::Vi    => None,
+        TagsKind::Emacs => from_args("etags-exe", "RUSTY_TAGS_ETAGS"),
+        TagsKind::Gtags  => return from_args("gtags-exe", "RUSTY_TAGS_GTAGS"),
+        TagsKind::Cscope => return from_args("cscope-exe", "RUSTY_TAGS_CSCOPE")
+    };
+
+    exe.or_else(|| from_args("ctags-exe", "RUSTY_TAGS_CTAGS"))
+       .or_else(|| file_config_exe.clone())
+}
+
+/// Searches for a ctags executable and detects its flavour by its '--version' output. An
+/// executable of unknown flavour is only used if there's no universal or exuberant ctags,
+/// then it's called with the options for exuberant ctags, the more conservative ones.
+/// For ctags the first line of the '--version' output is also returned.
+fn detect_tags_exe(ctags_exe: &Option<String>, kind: TagsKind, verbose: bool, output_to_stderr: bool) -> RtResult<(TagsExe, Option<String>)> {
+    if kind.is_database() {
+        return detect_database_exe(ctags_exe, kind).map(|exe| (exe, None));
+    }
+
+    let exes = if let Some(exe) = ctags_exe {
         vec![exe.as_str()]
     } else {
         vec!["ctags", "exuberant-ctags", "exctags", "universal-ctags", "uctags"]
     };
 
+    let mut unknown_exe = None;
     for exe in &exes {
         let mut cmd = Command::new(exe);
         cmd.arg("--version");
@@ -172,14 +1282,192 @@ fn detect_tags_exe(ctags_exe: &Option<String>) -> RtResult<TagsExe> {
         if let Ok(output) = cmd.output() {
             if output.status.success() {
                 let stdout = String::from_utf8_lossy(&output.stdout);
+                let version = stdout.lines().next().map(|l| l.trim().to_string());
                 if stdout.contains("Universal Ctags") {
-                    return Ok(TagsExe::UniversalCtags(exe.to_string()));
+                    return Ok((TagsExe::UniversalCtags(exe.to_string()), version));
+                }
+
+                if stdout.contains("Exuberant Ctags") {
+                    return Ok((TagsExe::ExuberantCtags(exe.to_string()), version));
+                }
+
+                if unknown_exe.is_none() {
+                    unknown_exe = Some((exe, version));
+                }
+            }
+        }
+    }
+
+    if let Some((exe, version)) = unknown_exe {
+        if verbose {
+            output!(output_to_stderr, "Couldn't detect if '{}' is universal or exuberant ctags, using the options of exuberant ctags", exe);
+        }
+
+        return Ok((TagsExe::ExuberantCtags(exe.to_string()), version));
+    }
+
+    Err(RtErr::MissingTagsExe { exes: exes.iter().map(|e| e.to_string()).collect(), kind })
+}
+
+/// Checks that the executable for the database of 'kind' - by default
+/// named 'gtags' for GNU Global and 'cscope' for cscope - is callable.
+fn detect_database_exe(exe: &Option<String>, kind: TagsKind) -> RtResult<TagsExe> {
+    let (default_exe, version_arg, tags_exe): (_, _, fn(String) -> TagsExe) = match kind {
+        TagsKind::Gtags  => ("gtags", "--version", TagsExe::GnuGlobal),
+        TagsKind::Cscope => ("cscope", "-V", TagsExe::Cscope),
+        _                => return Err(format!("Unexpected database kind: {}", kind).into())
+    };
+
+    let exe = exe.clone().unwrap_or(default_exe.to_string());
+    match Command::new(&exe).arg(version_arg).output() {
+        Ok(ref output) if output.status.success() => Ok(tags_exe(exe)),
+        _ => Err(RtErr::MissingTagsExe { exes: vec![exe], kind })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use toml;
+
+    #[cfg(unix)]
+    use test_support::{fake_ctags, test_config, test_dir, write_file};
+    use super::*;
+
+    const SAMPLE_PROJECT_CONFIG: &str = r#"
+kinds = ["vi", "emacs"]
+excludes = ["generated"]
+ctags_options = ["--fields=+n"]
+jobs = 3
+output_dir = "tags"
+std_lib_tags = false
+
+[vi]
+ctags_options = ["--kinds-Rust=-m"]
+
+[emacs]
+etags_options = ["--kinds-Rust=-c"]
+"#;
+
+    #[test]
+    fn parse_project_config() {
+        let config: ProjectConfig = toml::from_str(SAMPLE_PROJECT_CONFIG).unwrap();
+        assert_eq!(config.kinds().unwrap(), vec![TagsKind::Vi, TagsKind::Emacs]);
+        assert_eq!(config.excludes, Some(vec!["generated".to_string()]));
+        assert_eq!(config.ctags_options, Some(vec!["--fields=+n".to_string()]));
+        assert_eq!(config.jobs, Some(3));
+        assert_eq!(config.output_dir.as_deref(), Some(Path::new("tags")));
+        assert_eq!(config.std_lib_tags, Some(false));
+        assert_eq!(config.vi.and_then(|c| c.ctags_options), Some(vec!["--kinds-Rust=-m".to_string()]));
+        assert_eq!(config.emacs.and_then(|c| c.ctags_options), Some(vec!["--kinds-Rust=-c".to_string()]));
+    }
+
+    #[test]
+    fn project_config_rejects_unknown_fields() {
+        let err = toml::from_str::<ProjectConfig>("kinds = [\"vi\"]\nexclude = [\"target\"]\n").unwrap_err();
+        assert!(err.to_string().contains("unknown field `exclude`"), "{}", err);
+
+        let err = toml::from_str::<ProjectConfig>("[vi]\noptions = []\n").unwrap_err();
+        assert!(err.to_string().contains("unknown field `options`"), "{}", err);
+    }
+
+    #[test]
+    fn project_config_with_an_invalid_kind() {
+        let config: ProjectConfig = toml::from_str("kinds = [\"nvim\"]\n").unwrap();
+        assert!(config.kinds().unwrap_err().to_string().contains("Invalid tags kind 'nvim'"));
+    }
+
+    /// The arguments of the ctags command of 'config'.
+    #[cfg(unix)]
+    fn ctags_args(config: &Config) -> Vec<String> {
+        config.tags_spec.ctags_command().get_args().map(|a| a.to_string_lossy().into_owned()).collect()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn project_config_sets_the_defaults_of_the_arguments() {
+        let dir = test_dir("project_config_sets_the_defaults_of_the_arguments");
+        write_file(dir.join(PROJECT_CONFIG_FILE_NAME), SAMPLE_PROJECT_CONFIG);
+        let project_dir = dir.join("sub-dir");
+        fs::create_dir_all(&project_dir).unwrap();
+
+        let config = test_config(&project_dir, &[]);
+        assert_eq!(config.tags_specs.iter().map(|s| s.kind).collect::<Vec<_>>(), vec![TagsKind::Vi, TagsKind::Emacs]);
+        assert_eq!(config.num_threads, 3);
+        assert_eq!(config.output_dir, Some(dir.join("tags")));
+
+        let args = ctags_args(&config);
+        assert!(args.ends_with(&["--exclude=generated".to_string(), "--fields=+n".to_string(), "--kinds-Rust=-m".to_string()]),
+                "{:?}", args);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn arguments_override_the_project_config() {
+        let dir = test_dir("arguments_override_the_project_config");
+        write_file(dir.join(PROJECT_CONFIG_FILE_NAME), SAMPLE_PROJECT_CONFIG);
+        let output_dir = dir.join("other-tags");
+        let output_dir_arg = output_dir.to_string_lossy().into_owned();
+
+        let config = test_config(&dir, &["emacs", "--jobs", "2", "--exclude", "vendor", "--ctags-option=--fields=+l",
+                                         "--etags-option=--kinds-Rust=-t", "--output-dir", &output_dir_arg]);
+        assert_eq!(config.tags_specs.iter().map(|s| s.kind).collect::<Vec<_>>(), vec![TagsKind::Emacs]);
+        assert_eq!(config.num_threads, 2);
+        assert_eq!(config.output_dir, Some(output_dir));
+
+        let args = ctags_args(&config);
+        assert!(args.ends_with(&["--exclude=vendor".to_string(), "--fields=+l".to_string(), "--kinds-Rust=-t".to_string()]),
+                "{:?}", args);
+        assert!(! args.iter().any(|a| a == "--exclude=generated" || a == "--fields=+n" || a == "--kinds-Rust=-c"), "{:?}", args);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn invalid_project_config_is_an_error() {
+        let dir = test_dir("invalid_project_config_is_an_error");
+        write_file(dir.join(PROJECT_CONFIG_FILE_NAME), "jobs = \"many\"\n");
+        match ProjectConfig::load(&dir) {
+            Err(RtErr::TomlParse { file: Some(file), .. }) => assert_eq!(file, dir.join(PROJECT_CONFIG_FILE_NAME)),
+            other => panic!("Expected a parse error of the config file: {:?}", other.map(|_| ()))
+        }
+    }
+
+    #[test]
+    fn missing_ctags_exe() {
+        let exe = Some("/nonexistent/ctags".to_string());
+        match detect_tags_exe(&exe, TagsKind::Vi, false, false) {
+            Err(RtErr::MissingTagsExe { exes, kind: TagsKind::Vi }) => assert_eq!(exes, vec!["/nonexistent/ctags".to_string()]),
+            other => panic!("Expected a missing ctags executable: {:?}", other.map(|_| ()))
+        }
+    }
+
+    #[test]
+    fn missing_database_exe() {
+        for &kind in &[TagsKind::Gtags, TagsKind::Cscope] {
+            let exe = Some("/nonexistent/db".to_string());
+            match detect_tags_exe(&exe, kind, false, false) {
+                Err(RtErr::MissingTagsExe { exes, kind: err_kind }) => {
+                    assert_eq!(exes, vec!["/nonexistent/db".to_string()]);
+                    assert_eq!(err_kind, kind);
                 }
 
-                return Ok(TagsExe::ExuberantCtags(exe.to_string()));
+                other => panic!("Expected a missing executable for {}: {:?}", kind, other.map(|_| ()))
             }
         }
     }
 
-    Err(format!("Couldn't find 'ctags' executable! Searched for executables with names: {:?}. Is 'ctags' correctly installed?", &exes).into())
+    #[cfg(unix)]
+    #[test]
+    fn detect_universal_ctags() {
+        let exe = Some(fake_ctags().to_string_lossy().into_owned());
+        match detect_tags_exe(&exe, TagsKind::Vi, false, false) {
+            Ok((TagsExe::UniversalCtags(name), Some(version))) => {
+                assert_eq!(Some(name), exe);
+                assert!(version.starts_with("Universal Ctags"), "{}", version);
+            }
+
+            other => panic!("Expected universal ctags: {:?}", other.map(|(exe, _)| exe))
+        }
+    }
 }
@@ -1,88 +1,179 @@
-use std::path::Path;
+use std::fs;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::collections::VecDeque;
 
 use serde_json;
-use fnv::FnvHashMap;
+use fnv::{FnvHashMap, FnvHashSet};
+use semver::Version;
+use log::debug;
 
-use rt_result::RtResult;
-use types::{DepTree, Source, SourceVersion, SourceId};
+use rt_result::{RtErr, RtResult};
+use types::{DepTree, Source, SourceVersion, SourceId, SourceKind, TagsKind};
 use config::Config;
+use color::Style;
 
 type JsonValue = serde_json::Value;
 type JsonObject = serde_json::Map<String, JsonValue>;
 
 /// Returns the dependency tree of the whole cargo workspace.
 pub fn dependency_tree(config: &Config, metadata: &JsonValue) -> RtResult<DepTree> {
+    // the tags of '--dep' aren't written into the source directory of the dependency,
+    // which might be a shared one like the cargo registry
+    let dep_config;
+    let config = if config.dep.is_some() && config.output_dir.is_none() {
+        let workspace_root = as_str_from_value("workspace_root", metadata).map(PathBuf::from)?;
+        dep_config = Config { output_dir: Some(workspace_root), ..config.clone() };
+        &dep_config
+    } else {
+        config
+    };
+
     let mut dep_tree = DepTree::new();
-    let packages = packages(config, metadata, &mut dep_tree)?;
+    let (packages, missing_packages) = packages(config, metadata, &mut dep_tree)?;
+
+    debug!("Read {} packages with a source and {} packages without a source", packages.len(), missing_packages.len());
+    build_dep_tree(config, metadata, &packages, &missing_packages, &mut dep_tree)?;
+    debug!("Built dependency tree with {} sources and {} roots", dep_tree.all_sources().count(), dep_tree.roots().count());
+
+    // cscope creates a single database for the whole workspace
+    if config.workspace_tags || config.tags_spec.kind == TagsKind::Cscope {
+        let tags_dir = match config.output_dir {
+            Some(ref output_dir) => output_dir.as_path(),
+            None                 => as_str_from_value("workspace_root", metadata).map(Path::new)?
+        };
 
-    build_dep_tree(config, metadata, &packages, &mut dep_tree)?;
+        let tags_file = match config.single_file {
+            Some(ref file) => file.clone(),
+            None           => tags_dir.join(config.root_tags_file_name())
+        };
+
+        dep_tree.set_workspace_tags_file(tags_file);
+    }
 
     Ok(dep_tree)
 }
 
-fn workspace_members(metadata: &JsonValue) -> RtResult<Vec<SourceVersion>> {
+/// Returns the target directory of the cargo project, which cargo determines from
+/// 'CARGO_TARGET_DIR' or its configuration. If the metadata doesn't contain it -
+/// like the one of old cargo versions - it's 'CARGO_TARGET_DIR' or 'target' at
+/// the workspace root.
+pub fn target_dir(metadata: &JsonValue) -> RtResult<PathBuf> {
+    if let Some(dir) = metadata.get("target_directory").and_then(JsonValue::as_str) {
+        return Ok(PathBuf::from(dir));
+    }
+
+    // like cargo a relative 'CARGO_TARGET_DIR' is relative to the current directory
+    match env::var_os("CARGO_TARGET_DIR") {
+        Some(dir) => Ok(env::current_dir()?.join(dir)),
+        None      => Ok(Path::new(as_str_from_value("workspace_root", metadata)?).join("target"))
+    }
+}
+
+/// Returns the directories of the 'Cargo.toml' files of the workspace members.
+pub fn workspace_member_dirs(metadata: &JsonValue) -> RtResult<Vec<PathBuf>> {
+    let members = workspace_members(metadata)?;
+    let mut dirs = Vec::with_capacity(members.len());
+    for package in as_array_from_value("packages", metadata)? {
+        let id = as_str_from_value("id", package)?;
+        if ! members.contains(&id) {
+            continue;
+        }
+
+        let manifest_path = as_str_from_value("manifest_path", package).map(Path::new)?;
+        if let Some(dir) = manifest_path.parent() {
+            dirs.push(dir.to_path_buf());
+        }
+    }
+
+    Ok(dirs)
+}
+
+/// Returns the package ids of the workspace members.
+fn workspace_members(metadata: &JsonValue) -> RtResult<Vec<PackageId<'_>>> {
     let members = as_array_from_value("workspace_members", metadata)?;
-    let mut source_versions = Vec::with_capacity(members.len());
+    let mut ids = Vec::with_capacity(members.len());
     for member in members {
         let member_str = member.as_str()
             .ok_or(format!("Expected 'workspace_members' of type string but found: {}", to_string_pretty(member)))?;
 
-        source_versions.push(SourceVersion::parse_from_id(member_str)?)
+        ids.push(member_str);
     }
 
-    Ok(source_versions)
+    Ok(ids)
 }
 
+/// The opaque package id of 'cargo metadata'. Its format differs between
+/// cargo versions, so it's only used as a key and never parsed.
+type PackageId<'a> = &'a str;
+
 struct Package<'a> {
     pub source_id: SourceId,
-    pub source_path: &'a Path
+    pub source_version: SourceVersion<'a>,
+    pub source_path: PathBuf,
+    pub source_kind: SourceKind
 }
 
-type Packages<'a> = FnvHashMap<SourceVersion<'a>, Package<'a>>;
+type Packages<'a> = FnvHashMap<PackageId<'a>, Package<'a>>;
+
+/// The packages which sources couldn't be found.
+type MissingPackages<'a> = FnvHashMap<PackageId<'a>, SourceVersion<'a>>;
 
 fn packages<'a>(config: &Config,
                 metadata: &'a JsonValue,
                 dep_tree: &mut DepTree)
-                -> RtResult<Packages<'a>> {
+                -> RtResult<(Packages<'a>, MissingPackages<'a>)> {
     let packages = as_array_from_value("packages", metadata)?;
     dep_tree.reserve_num_sources(packages.len());
     let mut package_map = FnvHashMap::default();
+    let mut missing_packages = FnvHashMap::default();
     for package in packages {
         let id = as_str_from_value("id", package)?;
-        let source_version = SourceVersion::parse_from_id(id)?;
+        let source_version = {
+            let name = as_str_from_value("name", package)?;
+            let version = as_str_from_value("version", package)?;
+            SourceVersion::new(name, Version::parse(version)?)
+        };
 
-        let source_path = {
-            let path = source_path(config, package)?;
-            if path == None {
+        let source_path = match source_path(config, package) {
+            Ok(Some(path)) => path,
+            Ok(None)       => continue,
+            Err(err)       => {
+                verbose!(config, "{}", err);
+                missing_packages.insert(id, source_version);
                 continue;
             }
-
-            path.unwrap()
         };
 
-        verbose!(config, "Found package of {} with source at '{}'", source_version, source_path.display());
+        let source_kind = SourceKind::from_metadata(package.get("source").and_then(JsonValue::as_str));
+
+        verbose!(config, "Found {:?} package of {} with source at '{}'", source_kind, source_version, source_path.display());
 
         let source_id = dep_tree.new_source();
-        package_map.insert(source_version, Package { source_id, source_path });
+        package_map.insert(id, Package { source_id, source_version, source_path, source_kind });
     }
 
-    Ok(package_map)
+    Ok((package_map, missing_packages))
 }
 
 fn build_dep_tree(config: &Config,
                   metadata: &JsonValue,
                   packages: &Packages,
+                  missing_packages: &MissingPackages,
                   dep_tree: &mut DepTree)
                   -> RtResult<()> {
-    let root_ids = {
+    let mut root_ids = {
         let workspace_members = workspace_members(metadata)?;
-        verbose!(config, "Found workspace members: {:?}", workspace_members);
 
         let mut ids = Vec::with_capacity(workspace_members.len());
+        let mut versions = Vec::with_capacity(workspace_members.len());
         for member in &workspace_members {
-            ids.push(package(member, packages)?.source_id);
+            let member_package = package(member, packages)?;
+            ids.push(member_package.source_id);
+            versions.push(&member_package.source_version);
         }
 
+        verbose!(config, "Found workspace members: {:?}", versions);
         ids
     };
 
@@ -93,66 +184,363 @@ fn build_dep_tree(config: &Config,
         as_array_from_object("nodes", resolve)?
     };
 
+    let mut node_deps = Vec::with_capacity(nodes.len());
+    let mut node_missing_deps = FnvHashMap::default();
+    let mut all_deps = FnvHashMap::default();
     for node in nodes {
-        let node_version = {
+        let node_package = {
             let id = as_str_from_value("id", node)?;
-            SourceVersion::parse_from_id(id)?
+            match packages.get(id) {
+                Some(pkg) => pkg,
+                None      => continue
+            }
         };
 
-        let node_package = package(&node_version, packages)?;
+        let node_version = &node_package.source_version;
 
         let dep_ids = {
-            let dependencies = as_array_from_value("dependencies", node)?;
+            let dependencies = dependency_ids(config, node)?;
+            if config.crates.is_some() {
+                let ids: Vec<_> = dependencies.iter().filter_map(|id| packages.get(id)).map(|p| p.source_id).collect();
+                all_deps.insert(node_package.source_id, ids);
+            }
 
-            let dep_versions = {
-                let mut vers = Vec::with_capacity(dependencies.len());
-                for dep in dependencies {
-                    let id = dep.as_str()
-                        .ok_or(format!("Couldn't find string in dependency:\n{}", to_string_pretty(dep)))?;
+            let missing_deps: Vec<_> = dependencies.iter()
+                .filter_map(|id| missing_packages.get(id))
+                .filter(|version| is_dep_included(config, version))
+                .collect();
 
-                    vers.push(SourceVersion::parse_from_id(id)?);
+            if ! missing_deps.is_empty() {
+                node_missing_deps.insert(node_package.source_id, missing_deps);
+            }
+
+            let dep_packages = {
+                // packages without a source - or only with unsupported
+                // targets - aren't in 'packages' and are skipped
+                let mut pkgs = Vec::with_capacity(dependencies.len());
+                for id in &dependencies {
+                    if let Some(pkg) = packages.get(id) {
+                        if root_ids.contains(&pkg.source_id) || is_dep_included(config, &pkg.source_version) {
+                            pkgs.push(pkg);
+                        } else {
+                            verbose!(config, "Skipping filtered dependency: {}", pkg.source_version);
+                        }
+                    }
                 }
 
-                vers
+                pkgs
             };
 
-            if ! dep_versions.is_empty() {
+            if ! dep_packages.is_empty() {
+                let dep_versions: Vec<_> = dep_packages.iter().map(|p| &p.source_version).collect();
                 verbose!(config, "Found dependencies of {}: {:?}", node_version, dep_versions);
             }
 
-            let mut ids = Vec::with_capacity(dep_versions.len());
-            for version in &dep_versions {
-                ids.push(package(version, packages)?.source_id);
+            dep_packages.iter().map(|p| p.source_id).collect::<Vec<_>>()
+        };
+
+        node_deps.push((node_package, dep_ids));
+    }
+
+    // with '--crates-from' the listed crates become direct dependencies of the
+    // roots, so that the indirect ones are reachable without their unlisted parents
+    if config.crates.is_some() {
+        report_unknown_crates(config, packages, missing_packages);
+        let mut root_deps = listed_root_dependencies(config, &root_ids, packages, &all_deps);
+        for &mut (node_package, ref mut dep_ids) in &mut node_deps {
+            if let Some(deps) = root_deps.remove(&node_package.source_id) {
+                *dep_ids = deps;
             }
+        }
+    }
 
-            ids
-        };
+    // dev and build dependencies might be excluded, so not every
+    // package of 'cargo metadata' is a part of the dependency tree
+    let mut reachable_ids = reachable_source_ids(&root_ids, &node_deps);
 
+    // with '--dep' the dependency becomes the only root
+    if let Some(ref dep) = config.dep {
+        root_ids = vec![dep_source_id(dep, &root_ids, &reachable_ids, packages, missing_packages)?];
+        dep_tree.set_roots(root_ids.clone());
+        reachable_ids = reachable_source_ids(&root_ids, &node_deps);
+    }
+
+    // with '--depth' the sources at the deepest level are tagged without their dependencies
+    if let Some(depth) = config.depth {
+        let depths = source_depths(&root_ids, &node_deps);
+        for &mut (node_package, ref mut dep_ids) in &mut node_deps {
+            if depths.get(&node_package.source_id).is_none_or(|d| *d >= depth) {
+                dep_ids.clear();
+                node_missing_deps.remove(&node_package.source_id);
+            }
+        }
+
+        reachable_ids = reachable_source_ids(&root_ids, &node_deps);
+    }
+
+    // only the missing sources of the dependency tree are reported
+    {
+        let mut missing_deps: Vec<_> = node_missing_deps.iter()
+            .filter(|&(id, _)| reachable_ids.contains(id))
+            .flat_map(|(_, deps)| deps.iter().cloned())
+            .collect();
+
+        missing_deps.sort();
+        missing_deps.dedup();
+        for dep in missing_deps {
+            warn!(config, "Couldn't find the source of {}, so it's missing in the tags", Style::Missing.paint(config.colored, dep));
+            dep_tree.add_missing_source();
+        }
+
+        for (id, deps) in &node_missing_deps {
+            if reachable_ids.contains(id) {
+                let deps = deps.iter().map(|d| (d.name.to_string(), d.version.clone())).collect();
+                dep_tree.set_missing_dependencies(*id, deps);
+            }
+        }
+    }
+
+    for (node_package, dep_ids) in node_deps {
+        if ! reachable_ids.contains(&node_package.source_id) {
+            continue;
+        }
+
+        let node_version = &node_package.source_version;
         verbose!(config, "Building tree for {}", node_version);
 
-        let is_root = root_ids.iter().find(|id| **id == node_package.source_id) != None;
-        let source = Source::new(node_package.source_id, &node_version, node_package.source_path, is_root, config)?;
+        let is_root = root_ids.contains(&node_package.source_id);
+        if config.omit_deps {
+            if is_root {
+                let source = Source::new(node_package.source_id, node_version, &node_package.source_path,
+                                         node_package.source_kind, is_root, config)?;
+                dep_tree.set_source(source, Vec::new());
+            }
+
+            continue;
+        }
+
+        let source = Source::new(node_package.source_id, node_version, &node_package.source_path,
+                                 node_package.source_kind, is_root, config)?;
         dep_tree.set_source(source, dep_ids);
     }
 
     Ok(())
 }
 
-fn package<'a>(source_version: &SourceVersion<'a>, packages: &'a Packages) -> RtResult<&'a Package<'a>> {
-    packages.get(&source_version)
-        .ok_or(format!("Couldn't find package for {}", source_version).into())
+/// Returns the package ids of the dependencies of the resolve 'node',
+/// the dev and build dependencies are only included if enabled by 'config'.
+fn dependency_ids<'a>(config: &Config, node: &'a JsonValue) -> RtResult<Vec<PackageId<'a>>> {
+    // older cargo versions don't output the kinds of the dependencies
+    let deps = match node.get("deps").and_then(JsonValue::as_array) {
+        Some(deps) => deps,
+        None       => {
+            let dependencies = as_array_from_value("dependencies", node)?;
+            let mut ids = Vec::with_capacity(dependencies.len());
+            for dep in dependencies {
+                let id = dep.as_str()
+                    .ok_or(format!("Couldn't find string in dependency:\n{}", to_string_pretty(dep)))?;
+
+                ids.push(id);
+            }
+
+            return Ok(ids);
+        }
+    };
+
+    let mut ids = Vec::with_capacity(deps.len());
+    for dep in deps {
+        let id = as_str_from_value("pkg", dep)?;
+        let is_included = match dep.get("dep_kinds").and_then(JsonValue::as_array) {
+            Some(kinds) => kinds.iter().any(|kind| {
+                match kind.get("kind").and_then(JsonValue::as_str) {
+                    Some("dev")   => config.dev_deps,
+                    Some("build") => config.build_deps,
+                    _             => true
+                }
+            }),
+
+            None => true
+        };
+
+        if is_included {
+            ids.push(id);
+        } else {
+            verbose!(config, "Skipping dev or build dependency: {}", id);
+        }
+    }
+
+    Ok(ids)
 }
 
-fn source_path<'a>(config: &Config, package: &'a JsonValue) -> RtResult<Option<&'a Path>> {
+/// If the dependency isn't filtered by '--exclude-dep', '--only-dep' or '--crates-from'.
+fn is_dep_included(config: &Config, version: &SourceVersion) -> bool {
+    let matches_dep = |dep: &String| matches_crate(dep, version);
+    if let Some(ref crates) = config.crates {
+        return crates.iter().any(matches_dep);
+    }
+
+    if ! config.only_deps.is_empty() {
+        return config.only_deps.iter().any(matches_dep);
+    }
+
+    ! config.exclude_deps.iter().any(matches_dep)
+}
+
+/// If 'dep' - the name or 'name@version' of a crate - matches 'version'.
+fn matches_crate(dep: &str, version: &SourceVersion) -> bool {
+    match dep.split_once('@') {
+        Some((name, vers)) => name == version.name && vers == version.version.to_string(),
+        None               => dep == version.name
+    }
+}
+
+/// The id of the dependency 'dep' of '--dep' - given by its name or by 'name@version' -
+/// which has to be one of the 'reachable_ids' of the roots.
+fn dep_source_id(dep: &str,
+                 root_ids: &[SourceId],
+                 reachable_ids: &FnvHashSet<SourceId>,
+                 packages: &Packages,
+                 missing_packages: &MissingPackages)
+                 -> RtResult<SourceId> {
+    let mut matching: Vec<_> = packages.values()
+        .filter(|p| reachable_ids.contains(&p.source_id) && ! root_ids.contains(&p.source_id))
+        .filter(|p| matches_crate(dep, &p.source_version))
+        .collect();
+
+    match matching.len() {
+        1 => Ok(matching[0].source_id),
+
+        0 if missing_packages.values().any(|v| matches_crate(dep, v)) => {
+            Err(RtErr::MissingSource { name: dep.to_string() })
+        }
+
+        0 => Err(format!("'{}' given by '--dep' isn't a dependency of the cargo project", dep).into()),
+
+        _ => {
+            matching.sort_by(|a, b| a.source_version.version.cmp(&b.source_version.version));
+            let versions: Vec<_> = matching.iter().map(|p| format!("{}@{}", p.source_version.name, p.source_version.version)).collect();
+            Err(format!("Multiple versions of the dependency '{}' given by '--dep', select one of them: {}", dep, versions.join(", ")).into())
+        }
+    }
+}
+
+/// Reports the crates of '--crates-from' which aren't a package of the cargo project.
+fn report_unknown_crates(config: &Config, packages: &Packages, missing_packages: &MissingPackages) {
+    let crates = match config.crates {
+        Some(ref crates) => crates,
+        None             => return
+    };
+
+    let unknown_crates: Vec<_> = crates.iter()
+        .filter(|c| ! packages.values().any(|p| matches_crate(c, &p.source_version)) &&
+                    ! missing_packages.values().any(|v| matches_crate(c, v)))
+        .map(String::as_str)
+        .collect();
+
+    if ! unknown_crates.is_empty() {
+        warn!(config, "Unknown crates given by '--crates-from', they aren't dependencies of the cargo project: {}",
+              Style::Missing.paint(config.colored, unknown_crates.join(", ")));
+    }
+}
+
+/// The direct dependencies of the roots for '--crates-from': all direct and indirect
+/// dependencies of each root - following 'all_deps', the unfiltered dependencies of
+/// the sources - which are one of the listed crates.
+fn listed_root_dependencies(config: &Config,
+                            root_ids: &[SourceId],
+                            packages: &Packages,
+                            all_deps: &FnvHashMap<SourceId, Vec<SourceId>>)
+                            -> FnvHashMap<SourceId, Vec<SourceId>> {
+    let packages_by_id: FnvHashMap<SourceId, &Package> = packages.values().map(|p| (p.source_id, p)).collect();
+    let mut root_deps = FnvHashMap::default();
+    for root_id in root_ids {
+        let mut dep_ids = Vec::new();
+        let mut visited_ids = FnvHashSet::default();
+        let mut ids_to_visit = vec![*root_id];
+        while let Some(id) = ids_to_visit.pop() {
+            for dep_id in all_deps.get(&id).into_iter().flatten() {
+                if root_ids.contains(dep_id) || ! visited_ids.insert(*dep_id) {
+                    continue;
+                }
+
+                if packages_by_id.get(dep_id).is_some_and(|p| is_dep_included(config, &p.source_version)) {
+                    dep_ids.push(*dep_id);
+                }
+
+                ids_to_visit.push(*dep_id);
+            }
+        }
+
+        root_deps.insert(*root_id, dep_ids);
+    }
+
+    root_deps
+}
+
+/// Returns the ids of all sources reachable from the roots.
+fn reachable_source_ids(root_ids: &[SourceId], node_deps: &[(&Package, Vec<SourceId>)]) -> FnvHashSet<SourceId> {
+    let deps_by_id: FnvHashMap<SourceId, &Vec<SourceId>> = node_deps.iter()
+        .map(|&(pkg, ref deps)| (pkg.source_id, deps))
+        .collect();
+
+    let mut reachable_ids = FnvHashSet::default();
+    let mut ids_to_visit = root_ids.to_vec();
+    while let Some(id) = ids_to_visit.pop() {
+        if ! reachable_ids.insert(id) {
+            continue;
+        }
+
+        if let Some(deps) = deps_by_id.get(&id) {
+            ids_to_visit.extend(deps.iter().cloned());
+        }
+    }
+
+    reachable_ids
+}
+
+/// Returns the number of levels of the sources below the roots, over their shortest path.
+fn source_depths(root_ids: &[SourceId], node_deps: &[(&Package, Vec<SourceId>)]) -> FnvHashMap<SourceId, usize> {
+    let deps_by_id: FnvHashMap<SourceId, &Vec<SourceId>> = node_deps.iter()
+        .map(|&(pkg, ref deps)| (pkg.source_id, deps))
+        .collect();
+
+    let mut depths = FnvHashMap::default();
+    let mut ids_to_visit: VecDeque<_> = root_ids.iter().map(|id| (*id, 0)).collect();
+    while let Some((id, depth)) = ids_to_visit.pop_front() {
+        if depths.contains_key(&id) {
+            continue;
+        }
+
+        depths.insert(id, depth);
+        if let Some(deps) = deps_by_id.get(&id) {
+            ids_to_visit.extend(deps.iter().map(|dep_id| (*dep_id, depth + 1)));
+        }
+    }
+
+    depths
+}
+
+fn package<'a>(id: &PackageId<'a>, packages: &'a Packages) -> RtResult<&'a Package<'a>> {
+    packages.get(id)
+        .ok_or(format!("Couldn't find package for id '{}'", id).into())
+}
+
+fn source_path(config: &Config, package: &JsonValue) -> RtResult<Option<PathBuf>> {
     let targets = as_array_from_value("targets", package)?;
 
-    let manifest_dir = {
+    let metadata_manifest_dir = {
         let manifest_path = as_str_from_value("manifest_path", package).map(Path::new)?;
 
         manifest_path.parent()
             .ok_or(format!("Couldn't get directory of path '{:?}'", manifest_path.display()))?
     };
 
+    let manifest_dir = if metadata_manifest_dir.is_dir() {
+        metadata_manifest_dir.to_path_buf()
+    } else {
+        find_in_registries(config, package, metadata_manifest_dir).unwrap_or_else(|| metadata_manifest_dir.to_path_buf())
+    };
+
     for target in targets {
         let kinds = as_array_from_value("kind", target)?;
 
@@ -165,20 +553,33 @@ fn source_path<'a>(config: &Config, package: &'a JsonValue) -> RtResult<Option<&
                 continue;
             }
 
-            let mut src_path = as_str_from_value("src_path", target).map(Path::new)?;
+            let mut src_path = {
+                let src_path = as_str_from_value("src_path", target).map(Path::new)?;
+                match src_path.strip_prefix(metadata_manifest_dir) {
+                    Ok(path) => manifest_dir.join(path),
+                    Err(_)   => src_path.to_path_buf()
+                }
+            };
+
             if src_path.is_absolute() && src_path.is_file() {
                 src_path = src_path.parent()
                     .ok_or(format!("Couldn't get directory of path '{:?}' in target:\n{}\nof package:\n{}",
-                                   src_path.display(), to_string_pretty(target), to_string_pretty(package)))?;
+                                   src_path.display(), to_string_pretty(target), to_string_pretty(package)))?
+                    .to_path_buf();
             }
 
             if src_path.is_relative() {
-                src_path = manifest_dir;
+                src_path = manifest_dir.clone();
             }
 
             if ! src_path.is_dir() {
-                return Err(format!("Invalid source path directory '{:?}' in target:\n{}\nof package:\n{}",
-                                   src_path.display(), to_string_pretty(target), to_string_pretty(package)).into());
+                let mut msg = format!("Invalid source path directory '{:?}' in target:\n{}\nof package:\n{}",
+                                      src_path.display(), to_string_pretty(target), to_string_pretty(package));
+                if config.no_fetch {
+                    msg.push_str("\nThe fetching of the sources was disabled by '--no-fetch', so the source might not be downloaded yet.");
+                }
+
+                return Err(msg.into());
             }
 
             return Ok(Some(src_path));
@@ -188,8 +589,32 @@ fn source_path<'a>(config: &Config, package: &'a JsonValue) -> RtResult<Option<&
     Ok(None)
 }
 
+/// Searches the source of the registry 'package' - which isn't at 'manifest_dir' given by
+/// 'cargo metadata' - by its directory name '<name>-<version>' in the source directories
+/// of all registries, e.g. if it was downloaded from an alternate registry or by an older
+/// cargo version using a different directory for the registry.
+fn find_in_registries(config: &Config, package: &JsonValue, manifest_dir: &Path) -> Option<PathBuf> {
+    if SourceKind::from_metadata(package.get("source").and_then(JsonValue::as_str)) != SourceKind::Registry {
+        return None;
+    }
+
+    let dir_name = manifest_dir.file_name()?;
+    let registries_src_dir = manifest_dir.parent()?.parent()?;
+    debug!("Searching {:?} in the registries at '{}'", dir_name, registries_src_dir.display());
+    let mut dirs: Vec<PathBuf> = fs::read_dir(registries_src_dir).ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path().join(dir_name))
+        .filter(|dir| dir.join("Cargo.toml").is_file())
+        .collect();
+
+    dirs.sort();
+    let dir = dirs.into_iter().next()?;
+    verbose!(config, "Found the source of '{}' in another registry at '{}'", dir_name.to_string_lossy(), dir.display());
+    Some(dir)
+}
+
 fn to_string_pretty(value: &JsonValue) -> String {
-    serde_json::to_string_pretty(value).unwrap_or(String::new())
+    serde_json::to_string_pretty(value).unwrap_or_default()
 }
 
 fn as_array_from_value<'a>(entry: &str, value: &'a JsonValue) -> RtResult<&'a Vec<JsonValue>> {
@@ -215,3 +640,170 @@ fn as_array_from_object<'a>(entry: &str, object: &'a JsonObject) -> RtResult<&'a
           .and_then(JsonValue::as_array)
           .ok_or(format!("Couldn't find array entry '{}' in:\n{:?}", entry, object).into())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use semver::Version;
+    use serde_json::json;
+
+    #[cfg(unix)]
+    use test_support::{test_config, test_dir, write_file};
+    use super::*;
+
+    fn test_package(dep_tree: &mut DepTree, name: &'static str) -> Package<'static> {
+        Package {
+            source_id: dep_tree.new_source(),
+            source_version: SourceVersion::new(name, Version::parse("0.1.0").unwrap()),
+            source_path: PathBuf::from(name),
+            source_kind: SourceKind::Path
+        }
+    }
+
+    #[test]
+    fn depths_over_the_shortest_path() {
+        let mut dep_tree = DepTree::new();
+        let (root, a, b, c, unused) = (test_package(&mut dep_tree, "root"), test_package(&mut dep_tree, "a"), test_package(&mut dep_tree, "b"),
+                                       test_package(&mut dep_tree, "c"), test_package(&mut dep_tree, "unused"));
+
+        // root -> a -> b -> c, and the shortcut a -> c and the cycle c -> a
+        let node_deps = vec![
+            (&root, vec![a.source_id]),
+            (&a, vec![b.source_id, c.source_id]),
+            (&b, vec![c.source_id]),
+            (&c, vec![a.source_id]),
+            (&unused, vec![root.source_id])
+        ];
+
+        let depths = source_depths(&[root.source_id], &node_deps);
+        assert_eq!(depths.len(), 4);
+        assert_eq!(depths[&root.source_id], 0);
+        assert_eq!(depths[&a.source_id], 1);
+        assert_eq!(depths[&b.source_id], 2);
+        assert_eq!(depths[&c.source_id], 2);
+        assert!(! depths.contains_key(&unused.source_id));
+    }
+
+    /// The metadata of 'cargo metadata' for the workspace member 'root' with the
+    /// chain of dependencies 'root -> a -> b -> c', all crates are in 'dir'.
+    #[cfg(unix)]
+    fn chain_metadata(dir: &Path) -> JsonValue {
+        let names = ["root", "a", "b", "c"];
+        let id = |name: &str| format!("path+file://{}#{}@0.1.0", dir.join(name).display(), name);
+        let packages: Vec<_> = names.iter().map(|name| {
+            let crate_dir = dir.join(name);
+            write_file(crate_dir.join("src").join("lib.rs"), &format!("fn {}() {{}}\n", name));
+            json!({
+                "id": id(name),
+                "name": name,
+                "version": "0.1.0",
+                "source": null,
+                "manifest_path": crate_dir.join("Cargo.toml"),
+                "targets": [{ "kind": ["lib"], "src_path": crate_dir.join("src").join("lib.rs") }]
+            })
+        }).collect();
+
+        let nodes: Vec<_> = names.iter().enumerate().map(|(idx, name)| {
+            let deps: Vec<_> = names.get(idx + 1).into_iter()
+                .map(|dep| json!({ "pkg": id(dep), "dep_kinds": [{ "kind": null, "target": null }] }))
+                .collect();
+
+            json!({ "id": id(name), "deps": deps })
+        }).collect();
+
+        json!({
+            "packages": packages,
+            "resolve": { "nodes": nodes },
+            "workspace_members": [id("root")],
+            "workspace_root": dir.join("root")
+        })
+    }
+
+    /// The names of the sources of 'dep_tree' and of their dependencies.
+    #[cfg(unix)]
+    fn source_deps(dep_tree: &DepTree) -> Vec<(String, Vec<String>)> {
+        let mut sources: Vec<_> = dep_tree.all_sources()
+            .map(|s| (s.name.clone(), dep_tree.dependencies(s).map(|d| d.name.clone()).collect()))
+            .collect();
+
+        sources.sort();
+        sources
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn depth_cuts_off_the_dependencies() {
+        let dir = test_dir("depth_cuts_off_the_dependencies");
+        let metadata = chain_metadata(&dir);
+        let deps = |names: &[&str]| -> Vec<String> { names.iter().map(|n| n.to_string()).collect() };
+
+        let dep_tree = dependency_tree(&test_config(&dir, &["vi"]), &metadata).unwrap();
+        assert_eq!(source_deps(&dep_tree), vec![
+            ("a".to_string(), deps(&["b"])),
+            ("b".to_string(), deps(&["c"])),
+            ("c".to_string(), deps(&[])),
+            ("root".to_string(), deps(&["a"]))
+        ]);
+
+        let dep_tree = dependency_tree(&test_config(&dir, &["vi", "--depth", "2"]), &metadata).unwrap();
+        assert_eq!(source_deps(&dep_tree), vec![
+            ("a".to_string(), deps(&["b"])),
+            ("b".to_string(), deps(&[])),
+            ("root".to_string(), deps(&["a"]))
+        ]);
+
+        let dep_tree = dependency_tree(&test_config(&dir, &["vi", "--depth", "1"]), &metadata).unwrap();
+        assert_eq!(source_deps(&dep_tree), vec![("a".to_string(), deps(&[])), ("root".to_string(), deps(&["a"]))]);
+
+        let dep_tree = dependency_tree(&test_config(&dir, &["vi", "--depth", "0"]), &metadata).unwrap();
+        assert_eq!(source_deps(&dep_tree), vec![("root".to_string(), deps(&[]))]);
+    }
+
+    /// The metadata of the registry package 'foo 0.1.0' and its directory in the registry
+    /// 'index-hash' below 'dir', which doesn't exist. Only the registries 'registry_names'
+    /// contain the package.
+    #[cfg(unix)]
+    fn registry_package(dir: &Path, registry_names: &[&str]) -> (JsonValue, PathBuf) {
+        let registries_src_dir = dir.join("registry").join("src");
+        for name in registry_names {
+            write_file(registries_src_dir.join(name).join("foo-0.1.0").join("Cargo.toml"), "[package]\nname = \"foo\"\nversion = \"0.1.0\"\n");
+        }
+
+        let manifest_dir = registries_src_dir.join("index-hash").join("foo-0.1.0");
+        let package = json!({
+            "name": "foo",
+            "version": "0.1.0",
+            "source": "registry+https://github.com/rust-lang/crates.io-index",
+            "manifest_path": manifest_dir.join("Cargo.toml")
+        });
+
+        (package, manifest_dir)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn missing_registry_source_is_found_in_another_registry() {
+        let dir = test_dir("missing_registry_source_is_found_in_another_registry");
+        let (package, manifest_dir) = registry_package(&dir, &["b-hash"]);
+        let config = test_config(&dir, &["vi"]);
+        assert!(! manifest_dir.exists());
+        assert_eq!(find_in_registries(&config, &package, &manifest_dir),
+                   Some(dir.join("registry").join("src").join("b-hash").join("foo-0.1.0")));
+
+        // only registry packages are searched in the registries
+        let mut path_package = package.clone();
+        path_package["source"] = JsonValue::Null;
+        assert_eq!(find_in_registries(&config, &path_package, &manifest_dir), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn first_registry_in_sorted_order_wins() {
+        let dir = test_dir("first_registry_in_sorted_order_wins");
+        let (package, manifest_dir) = registry_package(&dir, &["b-hash", "a-hash"]);
+        let config = test_config(&dir, &["vi"]);
+        assert_eq!(find_in_registries(&config, &package, &manifest_dir),
+                   Some(dir.join("registry").join("src").join("a-hash").join("foo-0.1.0")));
+    }
+}
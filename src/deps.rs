@@ -0,0 +1,140 @@
+use std::path::PathBuf;
+
+use serde_json;
+use fnv::FnvHashSet;
+
+use rt_result::RtResult;
+use config::{Config, DepsConfig};
+use dependencies::dependency_tree;
+use types::{DepTree, Source, SourceKind};
+use fetch_source_and_metadata;
+
+/// The resolved dependencies of a cargo project root, printed by the 'deps' subcommand.
+#[derive(Serialize, Debug)]
+pub struct RootDependencies {
+    pub name: String,
+    pub version: String,
+    pub src_dir: PathBuf,
+
+    /// the direct and indirect dependencies of the root, sorted by their names
+    pub dependencies: Vec<Dependency>
+}
+
+#[derive(Serialize, Debug)]
+pub struct Dependency {
+    pub name: String,
+    pub version: String,
+    pub status: DependencyStatus,
+
+    /// the directory the tags are created from, none if the source is missing
+    pub src_dir: Option<PathBuf>
+}
+
+/// How the source of a dependency was resolved.
+#[derive(Serialize, PartialEq, Eq, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencyStatus {
+    /// the source of a registry package was found
+    Ok,
+
+    /// the source is a local path dependency
+    Path,
+
+    /// the source is a checkout of a git repository
+    Git,
+
+    /// the source couldn't be found, so it's missing in the tags
+    Missing
+}
+
+impl DependencyStatus {
+    fn name(self) -> &'static str {
+        match self {
+            DependencyStatus::Ok      => "OK",
+            DependencyStatus::Path    => "path",
+            DependencyStatus::Git     => "git",
+            DependencyStatus::Missing => "missing"
+        }
+    }
+}
+
+/// Prints the resolved dependencies of the roots of the cargo project,
+/// like the tags creation would read them, without creating any tags.
+pub fn print_dependencies(config: &DepsConfig) -> RtResult<()> {
+    let roots = resolve_dependencies(&config.config)?;
+    if config.json {
+        println!("{}", serde_json::to_string_pretty(&roots)?);
+        return Ok(());
+    }
+
+    for root in &roots {
+        println!("({}, {}) at '{}':", root.name, root.version, root.src_dir.display());
+        if root.dependencies.is_empty() {
+            println!("   no dependencies");
+        }
+
+        for dep in &root.dependencies {
+            match dep.src_dir {
+                Some(ref dir) => println!("   {:<8} ({}, {}) at '{}'", dep.status.name(), dep.name, dep.version, dir.display()),
+                None          => println!("   {:<8} ({}, {})", dep.status.name(), dep.name, dep.version)
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The resolved dependencies of the roots of the cargo project.
+pub fn resolve_dependencies(config: &Config) -> RtResult<Vec<RootDependencies>> {
+    let metadata = fetch_source_and_metadata(config)?;
+    let dep_tree = dependency_tree(config, &metadata)?;
+    Ok(dep_tree.roots().map(|root| root_dependencies(&dep_tree, root)).collect())
+}
+
+fn root_dependencies(dep_tree: &DepTree, root: &Source) -> RootDependencies {
+    let mut dependencies = Vec::new();
+    let mut visited_ids = FnvHashSet::default();
+    let mut sources_to_visit = vec![root];
+    while let Some(source) = sources_to_visit.pop() {
+        for (name, version) in dep_tree.missing_dependencies(source) {
+            dependencies.push(Dependency {
+                name: name.clone(),
+                version: version.to_string(),
+                status: DependencyStatus::Missing,
+                src_dir: None
+            });
+        }
+
+        for dep in dep_tree.dependencies(source) {
+            if dep.id == root.id || ! visited_ids.insert(dep.id) {
+                continue;
+            }
+
+            let status = match dep.kind {
+                SourceKind::Registry => DependencyStatus::Ok,
+                SourceKind::Path     => DependencyStatus::Path,
+                SourceKind::Git      => DependencyStatus::Git
+            };
+
+            dependencies.push(Dependency {
+                name: dep.name.clone(),
+                version: dep.version.to_string(),
+                status,
+                src_dir: Some(dep.dir.clone())
+            });
+
+            sources_to_visit.push(dep);
+        }
+    }
+
+    // a missing source might be a dependency of multiple sources
+    dependencies.sort_by(|a, b| (&a.name, &a.version, a.src_dir.as_ref()).cmp(&(&b.name, &b.version, b.src_dir.as_ref())));
+    dependencies.dedup_by(|a, b| a.name == b.name && a.version == b.version && a.src_dir == b.src_dir);
+
+    RootDependencies {
+        name: root.name.clone(),
+        version: root.version.to_string(),
+        src_dir: root.dir.clone(),
+        dependencies
+    }
+}
@@ -1,17 +1,43 @@
 use std::fs;
+use std::env;
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::hash::Hasher;
 use extern_dirs;
+use tempfile::NamedTempFile;
+use fnv::FnvHasher;
 
 use rt_result::RtResult;
 
 lazy_static! {
+    static ref RUSTY_TAGS_DIR_OVERRIDE: Mutex<Option<PathBuf>> = Mutex::new(None);
     static ref HOME_DIR: RtResult<PathBuf> = home_dir_internal();
     static ref RUSTY_TAGS_DIR: RtResult<PathBuf> = rusty_tags_dir_internal();
     static ref RUSTY_TAGS_CACHE_DIR: RtResult<PathBuf> = rusty_tags_cache_dir_internal();
     static ref RUSTY_TAGS_LOCKS_DIR: RtResult<PathBuf> = rusty_tags_locks_dir_internal();
 }
 
-/// where rusty-tags puts all of its stuff
+/// Overrides the directory where rusty-tags puts all of its stuff, this
+/// has to be called before any other function of this module is called.
+pub fn set_rusty_tags_dir(dir: PathBuf) {
+    if let Ok(mut override_dir) = RUSTY_TAGS_DIR_OVERRIDE.lock() {
+        *override_dir = Some(dir);
+    }
+}
+
+/// Checks that files can be created in the directory where rusty-tags puts all of its stuff
+pub fn check_rusty_tags_dir_writable() -> RtResult<()> {
+    let dir = rusty_tags_dir()?;
+    NamedTempFile::new_in(dir)
+        .map(|_| ())
+        .map_err(|err| format!("The rusty-tags directory '{}' isn't writable: {}\n\
+                                Use the environment variable 'RUSTY_TAGS_DIR' or the option '--cache-dir' \
+                                to use a different directory.", dir.display(), err).into())
+}
+
+/// where rusty-tags puts all of its stuff, by default '~/.rusty-tags',
+/// which can be changed by the environment variable 'RUSTY_TAGS_DIR'
+/// or by 'set_rusty_tags_dir'
 pub fn rusty_tags_dir() -> RtResult<&'static Path> {
     RUSTY_TAGS_DIR
         .as_ref()
@@ -27,6 +53,13 @@ pub fn rusty_tags_cache_dir() -> RtResult<&'static Path> {
         .map_err(|err| err.clone())
 }
 
+/// The tags file of the standard library for tags with the file extension 'extension'.
+/// It's in the rusty-tags directory, because the directory of the rust source - e.g.
+/// of a rustup toolchain - might not be writable.
+pub fn rust_std_lib_tags_file(extension: &str) -> RtResult<PathBuf> {
+    Ok(rusty_tags_dir()?.join(format!("rust-std-lib.{}", extension)))
+}
+
 /// where `rusty-tags` puts its locks when updating a cargo project
 pub fn rusty_tags_locks_dir() -> RtResult<&'static Path> {
     RUSTY_TAGS_LOCKS_DIR
@@ -35,6 +68,25 @@ pub fn rusty_tags_locks_dir() -> RtResult<&'static Path> {
         .map_err(|err| err.clone())
 }
 
+/// The definitions of the Rust language for '--rust-regex', which cover newer
+/// syntax than the definitions exuberant ctags gets by default.
+const RUST_REGEX_DEFINITIONS: &str = include_str!("rust.ctags");
+
+/// The ctags options file with the definitions of '--rust-regex', which is written into
+/// the rusty-tags directory. Its name contains the hash of the definitions, so that the
+/// ctags command - and therefore the cached tags - change with the definitions.
+pub fn rust_regex_file() -> RtResult<PathBuf> {
+    let mut hasher = FnvHasher::default();
+    hasher.write(RUST_REGEX_DEFINITIONS.as_bytes());
+    let file = rusty_tags_dir()?.join(format!("rust-regex-{}.ctags", hasher.finish()));
+    if ! file.is_file() {
+        fs::write(&file, RUST_REGEX_DEFINITIONS)
+            .map_err(|err| format!("Couldn't write the Rust definitions of '--rust-regex' to '{}': {}", file.display(), err))?;
+    }
+
+    Ok(file)
+}
+
 fn home_dir() -> RtResult<PathBuf> {
     HOME_DIR.clone()
 }
@@ -66,9 +118,18 @@ fn rusty_tags_locks_dir_internal() -> RtResult<PathBuf> {
 }
 
 fn rusty_tags_dir_internal() -> RtResult<PathBuf> {
-    let dir = home_dir()?.join(".rusty-tags");
+    let override_dir = RUSTY_TAGS_DIR_OVERRIDE.lock().ok().and_then(|dir| dir.clone());
+    let dir = if let Some(dir) = override_dir {
+        dir
+    } else if let Some(dir) = env::var_os("RUSTY_TAGS_DIR") {
+        env::current_dir()?.join(dir)
+    } else {
+        home_dir()?.join(".rusty-tags")
+    };
+
     if ! dir.is_dir() {
-        fs::create_dir_all(&dir)?;
+        fs::create_dir_all(&dir)
+            .map_err(|err| format!("Couldn't create the rusty-tags directory '{}': {}", dir.display(), err))?;
     }
 
     Ok(dir)
@@ -0,0 +1,706 @@
+//! The library of rusty-tags, which creates ctags/etags for a cargo project, all of
+//! its direct and indirect dependencies and the rust standard library.
+//!
+//! The binary is a thin wrapper around it: the command line arguments are parsed by
+//! `Action::from_command_args` - or `Action::from_args` for arguments given by the
+//! caller - and the tags are then created by `run`.
+//!
+//! ```no_run
+//! extern crate rusty_tags;
+//!
+//! use rusty_tags::{Action, run};
+//!
+//! # fn main() -> rusty_tags::RtResult<()> {
+//! if let Action::UpdateTags(config) = Action::from_args(&["rusty-tags", "vi", "--quiet"])? {
+//!     let summary = run(&config)?;
+//!     println!("exit code: {}", summary.exit_code);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+extern crate toml;
+extern crate tempfile;
+extern crate num_cpus;
+extern crate scoped_threadpool;
+extern crate serde;
+extern crate serde_json;
+extern crate fnv;
+extern crate semver;
+extern crate dirs as extern_dirs;
+extern crate glob;
+extern crate notify;
+extern crate log;
+
+#[macro_use]
+extern crate serde_derive;
+
+#[macro_use]
+extern crate clap;
+
+#[macro_use]
+extern crate lazy_static;
+
+use std::path::{Path, PathBuf};
+use std::fs::{self, File};
+use std::process::{Command, Output};
+use std::hash::Hasher;
+use std::env;
+use std::cmp::{max, min};
+use std::thread;
+use std::time::Duration;
+
+use tempfile::NamedTempFile;
+use fnv::{FnvHashSet, FnvHasher};
+use log::debug;
+
+use dependencies::{dependency_tree, target_dir};
+use tags::{update_tags, create_tags, move_tags, path_bytes};
+use watch::{watch_tags, affected_root_ids};
+use process::output_with_timeout;
+use types::{CacheLock, SourceLock, ctags_command_hash, find_dir_upwards_containing};
+use dirs::rusty_tags_cache_dir;
+use color::Style;
+
+pub use rt_result::{RtErr, RtResult};
+pub use config::{Action, Config, CleanConfig, DepsConfig, print_completions};
+pub use clean::clean;
+pub use deps::{print_dependencies, resolve_dependencies, RootDependencies, Dependency, DependencyStatus};
+pub use types::{TagsKind, TagsSpec, TagsExe, UpdateStatus, TagsSummary, RootSummary, FailureSummary};
+
+#[macro_use]
+mod output;
+
+mod rt_result;
+mod dependencies;
+mod dirs;
+mod tags;
+mod types;
+mod config;
+mod clean;
+mod deps;
+mod watch;
+mod process;
+mod progress;
+mod color;
+
+#[cfg(test)]
+mod test_support;
+
+/// Creates the tags of all kinds of 'config.tags_specs' for the cargo project
+/// and its dependencies, like the binary does for the given 'config'. With
+/// 'config.watch' this only returns if the watching failed.
+pub fn run(config: &Config) -> RtResult<RunSummary> {
+    if config.std_only {
+        return update_only_std_lib_tags(config);
+    }
+
+    if ! config.projects.is_empty() {
+        let mut summary = update_projects(config)?;
+        return match missing_sources_error(config, &mut summary) {
+            Some(err) => Err(err),
+            None      => Ok(summary)
+        };
+    }
+
+    let metadata = fetch_source_and_metadata(config)?;
+    let mut summary = update_project_tags(config, &metadata, &mut FnvHashSet::default())?;
+    let missing_error = missing_sources_error(config, &mut summary);
+    if config.summary_json {
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+    }
+
+    if let Some(err) = missing_error {
+        return Err(err);
+    }
+
+    if config.watch {
+        watch_tags(config, &metadata)?;
+    }
+
+    Ok(summary)
+}
+
+/// With '--fail-on-missing' the error if the sources of some dependencies couldn't
+/// be found, then the exit code of 'summary' is set to the one of an error.
+fn missing_sources_error(config: &Config, summary: &mut RunSummary) -> Option<RtErr> {
+    if ! config.fail_on_missing || summary.kinds.iter().all(|k| k.missing_sources == 0) {
+        return None;
+    }
+
+    summary.exit_code = 1;
+    Some("The sources of some dependencies couldn't be found, failing because of '--fail-on-missing'".into())
+}
+
+/// The summary of the whole run, printed by '--summary-json'.
+#[derive(Serialize, Debug)]
+pub struct RunSummary {
+    /// the exit code of the binary for this run
+    pub exit_code: i32,
+
+    /// the status of the whole run, the worst status of all kinds
+    #[serde(skip)]
+    pub status: UpdateStatus,
+
+    /// the summaries of the created kinds of tags
+    pub kinds: Vec<TagsSummary>
+}
+
+/// Updates the tags of all kinds of 'config.tags_specs' for the cargo project of 'metadata'.
+/// The tags of the standard library are only updated for the kinds which aren't
+/// already in 'std_lib_kinds', which is shared by the projects of '--projects'.
+fn update_project_tags(config: &Config,
+                       metadata: &serde_json::Value,
+                       std_lib_kinds: &mut FnvHashSet<String>)
+                       -> RtResult<RunSummary> {
+    let mut kinds = Vec::with_capacity(config.tags_specs.len());
+    let mut status = UpdateStatus::Complete;
+    for tags_spec in &config.tags_specs {
+        let mut kind_config = config.for_tags_spec(tags_spec);
+        if ! std_lib_kinds.insert(format!("{}-{}", tags_spec.file_name(), ctags_command_hash(tags_spec))) {
+            kind_config.std_lib_tags = false;
+        }
+
+        let summary = update_tags_of_kind(&kind_config, metadata, None)?;
+        status = max(status, summary.status());
+        kinds.push(summary);
+    }
+
+    Ok(RunSummary { exit_code: status.exit_code(), status, kinds })
+}
+
+/// Updates the tags of the cargo projects of '--projects' one after another. With
+/// '--keep-going' a failed project doesn't stop the others. At the end the status
+/// of every project is printed.
+fn update_projects(config: &Config) -> RtResult<RunSummary> {
+    let mut std_lib_kinds = FnvHashSet::default();
+    let mut project_statuses = Vec::with_capacity(config.projects.len());
+    let mut kinds = Vec::new();
+    let mut status = UpdateStatus::Complete;
+    for project in &config.projects {
+        info!(config, "\nUpdating the cargo project at '{}' ...", project.start_dir.display());
+        let result = fetch_source_and_metadata(project)
+            .and_then(|metadata| update_project_tags(project, &metadata, &mut std_lib_kinds));
+
+        match result {
+            Ok(summary) => {
+                status = max(status, summary.status);
+                project_statuses.push((&project.start_dir, Ok(summary.status)));
+                kinds.extend(summary.kinds);
+            }
+
+            Err(err) => {
+                if ! config.keep_going {
+                    return Err(err);
+                }
+
+                warn!(config, "{}, continuing because of '--keep-going':\n{}",
+                      Style::Error.paint(config.colored, format!("Couldn't update the cargo project at '{}'", project.start_dir.display())),
+                      err.to_string().trim_end());
+
+                status = UpdateStatus::FailedSources;
+                project_statuses.push((&project.start_dir, Err(err)));
+            }
+        }
+    }
+
+    if ! config.quiet {
+        output!(config.output_to_stderr, "\nSummary of {} cargo projects:", project_statuses.len());
+        for (dir, project_status) in &project_statuses {
+            let status = match *project_status {
+                Ok(UpdateStatus::Complete)       => "OK".to_string(),
+                Ok(UpdateStatus::MissingSources) => Style::Missing.paint(config.colored, "missing sources"),
+                Ok(UpdateStatus::FailedSources)  => Style::Error.paint(config.colored, "failed sources"),
+                Err(ref err)                     => {
+                    let err = err.to_string();
+                    Style::Error.paint(config.colored, format!("failed: {}", err.lines().next().unwrap_or("")))
+                }
+            };
+
+            output!(config.output_to_stderr, "   {}: {}", Style::Name.paint(config.colored, dir.display()), status);
+        }
+    }
+
+    Ok(RunSummary { exit_code: status.exit_code(), status, kinds })
+}
+
+/// Recreates the tags of the standard library - the 'rust-std-lib.<ext>' files of the
+/// rusty-tags directory - for all kinds of 'config.tags_specs', without reading the
+/// metadata of the cargo project.
+fn update_only_std_lib_tags(config: &Config) -> RtResult<RunSummary> {
+    let _cache_lock = CacheLock::acquire(config)?;
+    let mut kinds = Vec::with_capacity(config.tags_specs.len());
+    for tags_spec in &config.tags_specs {
+        if tags_spec.kind.is_database() {
+            warn!(config, "The {} database doesn't contain the standard library, so nothing is created for it", tags_spec.kind);
+        } else {
+            update_std_lib_tags(&config.for_tags_spec(tags_spec))?;
+        }
+
+        kinds.push(TagsSummary::new(tags_spec.kind));
+    }
+
+    let summary = RunSummary { exit_code: 0, status: UpdateStatus::Complete, kinds };
+    if config.summary_json {
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+    }
+
+    Ok(summary)
+}
+
+/// Updates the tags of the kind 'config.tags_spec'. If 'changed_files' are
+/// given, then only the roots are updated which depend on these files.
+fn update_tags_of_kind(config: &Config,
+                       metadata: &serde_json::Value,
+                       changed_files: Option<&[PathBuf]>)
+                       -> RtResult<TagsSummary> {
+    let artifacts_config;
+    let config = if config.artifacts_in_target {
+        let dir = target_dir(metadata)?.join("rusty-tags");
+        if ! dir.is_dir() && ! config.dry_run {
+            fs::create_dir_all(&dir)
+                .map_err(|err| format!("Couldn't create the directory '{}' for the cached tags: {}", dir.display(), err))?;
+        }
+
+        artifacts_config = Config { artifacts_dir: Some(dir), ..config.clone() };
+        &artifacts_config
+    } else {
+        config
+    };
+
+    if config.dry_run {
+        update_std_lib_tags(config)?;
+        return update_tags(config, &dependency_tree(config, metadata)?);
+    }
+
+    let _cache_lock = CacheLock::acquire(config)?;
+    update_std_lib_tags(config)?;
+
+    let mut source_locks = Vec::new();
+    let dep_tree = {
+        let mut dep_tree = dependency_tree(config, metadata)?;
+        if let Some(changed_files) = changed_files {
+            let root_ids = affected_root_ids(config, &dep_tree, changed_files);
+            dep_tree.set_roots(root_ids);
+        }
+
+        let unlocked_root_ids: Vec<_> = {
+            let mut unlocked_roots = Vec::new();
+            for source in dep_tree.roots() {
+                match source.lock(&config.tags_spec)? {
+                    SourceLock::AlreadyLocked { ref path } => {
+                        warn!(config, "Already creating tags for '{}', if this isn't the case remove the lock file '{}'",
+                              source.name, path.display());
+                        continue;
+                    }
+
+                    sl@SourceLock::Locked { .. } => {
+                        source_locks.push(sl);
+                        unlocked_roots.push(source);
+                    }
+                }
+            }
+
+            unlocked_roots.iter().map(|r| r.id).collect()
+        };
+
+        if unlocked_root_ids.is_empty() {
+            return Ok(TagsSummary::new(config.tags_spec.kind));
+        }
+
+        dep_tree.set_roots(unlocked_root_ids);
+        dep_tree
+    };
+
+    update_tags(config, &dep_tree)
+}
+
+fn fetch_source_and_metadata(config: &Config) -> RtResult<serde_json::Value> {
+    env::set_current_dir(&config.start_dir)?;
+
+    // the sources of an unchanged 'Cargo.lock' were already fetched by a previous
+    // run, so the metadata is only read, but fetched if this fails anyway
+    let last_fetch = if config.no_fetch { None } else { fetch_record(config) };
+    if let Some((ref record_file, ref lock_hash)) = last_fetch {
+        if ! config.force_recreate && fs::read_to_string(record_file).ok().as_ref() == Some(lock_hash) {
+            info!(config, "Reading metadata ...");
+            match run_cargo_metadata(config, true) {
+                Ok(output) if output.status.success() => {
+                    return Ok(serde_json::from_str(&String::from_utf8_lossy(&output.stdout))?);
+                }
+
+                Ok(output) => verbose!(config, "Reading the metadata of the unchanged 'Cargo.lock' failed, fetching it:\n{}",
+                                       String::from_utf8_lossy(&output.stderr).trim_end()),
+
+                Err(err) => return Err(err)
+            }
+        }
+    }
+
+    if config.no_fetch {
+        info!(config, "Reading metadata ...");
+    } else {
+        info!(config, "Fetching source and metadata ...");
+    }
+
+    // a missing cargo or a timeout isn't retried, only a failed
+    // execution, which might be caused by a flaky network
+    let mut num_retries = 0;
+    let output = loop {
+        let output = run_cargo_metadata(config, config.no_fetch)?;
+        if output.status.success() || config.no_fetch || num_retries >= config.fetch_retries {
+            break output;
+        }
+
+        num_retries += 1;
+        let delay = Duration::from_secs(1 << min(num_retries - 1, 5));
+        verbose!(config, "Fetching source and metadata failed, retry {} of {} in {} seconds:\n{}",
+                 num_retries, config.fetch_retries, delay.as_secs(), String::from_utf8_lossy(&output.stderr).trim_end());
+
+        thread::sleep(delay);
+    };
+
+    if ! output.status.success() {
+        let mut stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        if stderr.trim().is_empty() {
+            stderr = String::from_utf8_lossy(&output.stdout).into_owned();
+        }
+
+        return Err(RtErr::CommandFailed {
+            command: "cargo metadata".to_string(),
+            target: format!("the cargo project at '{}'", config.start_dir.display()),
+            status: output.status,
+            stderr
+        });
+    }
+
+    // the lock file might have been created or updated by cargo, so the record
+    // is determined again, the fetching itself succeeded nevertheless
+    if ! config.no_fetch {
+        if let Some((record_file, lock_hash)) = fetch_record(config) {
+            if let Err(err) = fs::write(&record_file, lock_hash) {
+                verbose!(config, "Couldn't write '{}': {}", record_file.display(), err);
+            }
+        }
+    }
+
+    Ok(serde_json::from_str(&String::from_utf8_lossy(&output.stdout))?)
+}
+
+/// Runs 'cargo metadata' for the cargo project and the dependencies selected by 'config'.
+/// With 'offline' the sources of the dependencies aren't fetched.
+fn run_cargo_metadata(config: &Config, offline: bool) -> RtResult<Output> {
+    let mut cmd = metadata_command(config, offline);
+    debug!("Running {:?} in '{}'", cmd, config.start_dir.display());
+    output_with_timeout(&mut cmd, config.timeout)
+        .map_err(|err| format!("'cargo' execution failed: {}\nIs 'cargo' correctly installed?", err))?
+        .ok_or_else(|| RtErr::Timeout {
+            command: "cargo metadata".to_string(),
+            target: format!("the cargo project at '{}'", config.start_dir.display()),
+            secs: config.timeout.map(|t| t.as_secs()).unwrap_or(0)
+        })
+}
+
+fn metadata_command(config: &Config, offline: bool) -> Command {
+    let mut cmd = Command::new("cargo");
+    cmd.arg("metadata");
+    cmd.arg("--format-version=1");
+    if let Some(ref manifest_path) = config.manifest_path {
+        cmd.arg("--manifest-path").arg(manifest_path);
+    }
+
+    if offline {
+        cmd.arg("--offline");
+    }
+
+    if ! config.features.is_empty() {
+        cmd.arg("--features").arg(config.features.join(","));
+    }
+
+    if config.no_default_features {
+        cmd.arg("--no-default-features");
+    }
+
+    if config.all_features {
+        cmd.arg("--all-features");
+    }
+
+    if ! config.all_targets {
+        let target = match config.target {
+            Some(ref target) => Some(target.clone()),
+            None             => host_target()
+        };
+
+        if let Some(target) = target {
+            verbose!(config, "Only including the dependencies of the target: {}", target);
+            cmd.arg("--filter-platform").arg(target);
+        } else {
+            verbose!(config, "Couldn't determine the host target, including the dependencies of all targets");
+        }
+    }
+
+    cmd
+}
+
+/// The file recording the last successful fetch of the sources of the 'Cargo.lock' of the cargo
+/// project and the hash of the 'Cargo.lock' and of the fetch arguments. None if there's no 'Cargo.lock'.
+fn fetch_record(config: &Config) -> Option<(PathBuf, String)> {
+    let manifest_dir = match config.manifest_path {
+        Some(ref manifest_path) => manifest_path.parent()?.to_path_buf(),
+        None                    => config.start_dir.clone()
+    };
+
+    let lock_dir = find_dir_upwards_containing("Cargo.lock", &env::current_dir().ok()?.join(manifest_dir)).ok()?;
+    let lock_file = lock_dir.join("Cargo.lock");
+    let lock_contents = fs::read(&lock_file).ok()?;
+
+    // FNV-1a hashes of bytes, which - unlike the 'DefaultHasher' - don't change
+    // between rust releases, so a toolchain update doesn't force a new fetch
+    let mut hasher = FnvHasher::default();
+    hasher.write(&lock_contents);
+    for feature in &config.features {
+        hasher.write_u8(0);
+        hasher.write(feature.as_bytes());
+    }
+
+    for option in &[config.no_default_features, config.all_features, config.target.is_some(), config.all_targets] {
+        hasher.write_u8(*option as u8);
+    }
+
+    if let Some(ref target) = config.target {
+        hasher.write(target.as_bytes());
+    }
+
+    let lock_hash = hasher.finish().to_string();
+
+    let mut path_hasher = FnvHasher::default();
+    path_hasher.write(&path_bytes(&lock_file));
+    let record_file = rusty_tags_cache_dir().ok()?.join(format!("Cargo.lock-{}.fetched", path_hasher.finish()));
+    debug!("Fetch record of '{}' is '{}'", lock_file.display(), record_file.display());
+    Some((record_file, lock_hash))
+}
+
+/// The target triple of the host, read from the output of 'rustc -vV'.
+fn host_target() -> Option<String> {
+    let output = Command::new("rustc").arg("-vV").output().ok()?;
+    if ! output.status.success() {
+        return None;
+    }
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find(|line| line.starts_with("host: "))
+        .map(|line| line["host: ".len()..].trim().to_string())
+}
+
+/// The directory of the rust standard library source. Searched in the order: the
+/// environment variable 'RUST_SRC_PATH', the sysroot of the current rust compiler
+/// and the 'rust-src' component of the active rustup toolchain.
+fn rust_std_lib_src_dir() -> RtResult<PathBuf> {
+    if let Some(src_path) = env::var_os("RUST_SRC_PATH") {
+        let src_path = PathBuf::from(src_path);
+        if ! src_path.is_dir() {
+            return Err(format!("Missing rust source code at '{}'!", src_path.display()).into());
+        }
+
+        return Ok(src_path);
+    }
+
+    find_rust_src_dir(&sysroots())
+}
+
+/// The sysroot of the current rust compiler and the one of the active rustup toolchain.
+fn sysroots() -> Vec<PathBuf> {
+    let mut sysroots = Vec::new();
+    if let Some(sysroot) = command_output("rustc", &["--print", "sysroot"]) {
+        sysroots.push(PathBuf::from(sysroot));
+    }
+
+    let toolchain = command_output("rustup", &["show", "active-toolchain"])
+        .and_then(|out| out.split_whitespace().next().map(str::to_string));
+
+    let rustup_home = env::var_os("RUSTUP_HOME")
+        .map(PathBuf::from)
+        .or_else(|| extern_dirs::home_dir().map(|home| home.join(".rustup")));
+
+    if let (Some(toolchain), Some(rustup_home)) = (toolchain, rustup_home) {
+        sysroots.push(rustup_home.join("toolchains").join(toolchain));
+    }
+
+    sysroots
+}
+
+/// The first existing directory of the rust source inside of 'sysroots'.
+fn find_rust_src_dir<P: AsRef<Path>>(sysroots: &[P]) -> RtResult<PathBuf> {
+    let searched_dirs: Vec<_> = sysroots.iter().flat_map(|s| rust_src_dirs(s.as_ref())).collect();
+    match searched_dirs.iter().find(|dir| dir.is_dir()) {
+        Some(dir) => Ok(dir.clone()),
+        None      => Err(RtErr::MissingStdLibSrc { searched_dirs })
+    }
+}
+
+/// The possible directories of the rust source inside of the sysroot 'sysroot',
+/// the source is in 'library' since rust 1.47, before it was in 'src'.
+fn rust_src_dirs(sysroot: &Path) -> Vec<PathBuf> {
+    let rust_src = sysroot.join("lib").join("rustlib").join("src").join("rust");
+    vec![rust_src.join("library"), rust_src.join("src")]
+}
+
+/// The trimmed stdout of the successful execution of 'cmd' with 'args'.
+fn command_output(cmd: &str, args: &[&str]) -> Option<String> {
+    match Command::new(cmd).args(args).output() {
+        Ok(ref output) if output.status.success() => {
+            Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        }
+
+        _ => None
+    }
+}
+
+fn update_std_lib_tags(config: &Config) -> RtResult<()> {
+    // the GNU Global and cscope databases only contain the sources of the cargo project
+    if config.tags_spec.kind.is_database() || ! (config.std_lib_tags || config.std_only) {
+        return Ok(());
+    }
+
+    let src_path = match rust_std_lib_src_dir() {
+        Ok(src_path) => src_path,
+
+        // the tags of the standard library are optional, so only hint once about the
+        // missing source, otherwise every run would be noisy. With '--quiet' the hint
+        // is kept for a later run, '--ignore-std-missing' marks it as shown without showing it.
+        Err(err@RtErr::MissingStdLibSrc { .. }) if ! config.std_only => {
+            let hint_file = dirs::rusty_tags_dir()?.join("std-lib-hint-shown");
+            if ! hint_file.is_file() && ! config.dry_run && (config.ignore_std_missing || ! config.quiet) {
+                if ! config.ignore_std_missing {
+                    info!(config, "{}No tags for the standard library are created.", err);
+                }
+
+                let _ = File::create(hint_file);
+            }
+
+            return Ok(());
+        }
+
+        Err(err) => return Err(err)
+    };
+
+    // the tags are shared by all toolchains, so they're recreated if the
+    // ctags command or the directory of the rust source changed
+    let src_path = src_path.as_path();
+    let extension = config.tags_spec.file_extension();
+    let std_lib_tags = dirs::rust_std_lib_tags_file(extension)?;
+    let record_file = std_lib_tags.with_extension(format!("{}.hash", extension));
+    let record = format!("{}\n{}", ctags_command_hash(&config.tags_spec), src_path.display());
+    let is_same_record = fs::read_to_string(&record_file).ok().as_ref() == Some(&record);
+    if std_lib_tags.is_file() && is_same_record && ! config.force_recreate && ! config.std_only {
+        return Ok(());
+    }
+
+    let possible_src_dirs = [
+        // the layout since rust 1.47
+        "alloc",
+        "backtrace",
+        "core",
+        "panic_abort",
+        "panic_unwind",
+        "portable-simd",
+        "proc_macro",
+        "std",
+        "std_detect",
+        "test",
+        "unwind",
+
+        // the old layout
+        "liballoc",
+        "libarena",
+        "libbacktrace",
+        "libcollections",
+        "libcore",
+        "libflate",
+        "libfmt_macros",
+        "libgetopts",
+        "libgraphviz",
+        "liblog",
+        "librand",
+        "librbml",
+        "libserialize",
+        "libstd",
+        "libsyntax",
+        "libterm"
+    ];
+
+    let mut src_dirs = Vec::new();
+    for dir in &possible_src_dirs {
+        let src_dir = src_path.join(dir);
+        if src_dir.is_dir() {
+            src_dirs.push(src_dir);
+        }
+    }
+
+    if config.dry_run {
+        info!(config, "Would create tags for the standard library at '{}'", std_lib_tags.display());
+        return Ok(());
+    }
+
+    info!(config, "Creating tags for the standard library ...");
+
+    let tmp_std_lib_tags = NamedTempFile::new_in(dirs::rusty_tags_dir()?)?;
+    create_tags(config, &src_dirs, tmp_std_lib_tags.path())?;
+    move_tags(config, tmp_std_lib_tags.path(), &std_lib_tags)?;
+    fs::write(&record_file, record)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use test_support::test_dir;
+    use super::*;
+
+    #[test]
+    fn rust_src_dir_of_a_sysroot() {
+        let dir = test_dir("rust_src_dir_of_a_sysroot");
+        let (old, new, empty) = (dir.join("old"), dir.join("new"), dir.join("empty"));
+        let old_src = old.join("lib").join("rustlib").join("src").join("rust").join("src");
+        let new_src = new.join("lib").join("rustlib").join("src").join("rust").join("library");
+        fs::create_dir_all(&old_src).unwrap();
+        fs::create_dir_all(&new_src).unwrap();
+        fs::create_dir_all(&empty).unwrap();
+
+        assert_eq!(find_rust_src_dir(&[&new]).unwrap(), new_src);
+        assert_eq!(find_rust_src_dir(&[&old]).unwrap(), old_src);
+
+        // the first sysroot containing the source wins
+        assert_eq!(find_rust_src_dir(&[&empty, &old, &new]).unwrap(), old_src);
+
+        // 'library' is preferred over 'src'
+        fs::create_dir_all(old.join("lib").join("rustlib").join("src").join("rust").join("library")).unwrap();
+        assert_eq!(find_rust_src_dir(&[&old]).unwrap(), old_src.with_file_name("library"));
+    }
+
+    #[test]
+    fn missing_rust_src_dir() {
+        let dir = test_dir("missing_rust_src_dir");
+        let rust_src = dir.join("lib").join("rustlib").join("src").join("rust");
+
+        // a file isn't a source directory
+        fs::create_dir_all(&rust_src).unwrap();
+        fs::write(rust_src.join("library"), "").unwrap();
+
+        match find_rust_src_dir(&[&dir]) {
+            Err(RtErr::MissingStdLibSrc { searched_dirs }) => {
+                assert_eq!(searched_dirs, vec![rust_src.join("library"), rust_src.join("src")]);
+            }
+
+            other => panic!("Expected the missing source of the standard library: {:?}", other)
+        }
+
+        match find_rust_src_dir::<&Path>(&[]) {
+            Err(RtErr::MissingStdLibSrc { searched_dirs }) => assert!(searched_dirs.is_empty()),
+            other => panic!("Expected the missing source of the standard library: {:?}", other)
+        }
+    }
+}
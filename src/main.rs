@@ -1,173 +1,69 @@
-//#![allow(dead_code)]
-//#![allow(unused_variables)]
+extern crate rusty_tags;
+extern crate env_logger;
+extern crate log;
 
-extern crate toml;
-extern crate tempfile;
-extern crate num_cpus;
-extern crate scoped_threadpool;
-extern crate serde;
-extern crate serde_json;
-extern crate fnv;
-extern crate semver;
-extern crate dirs as extern_dirs;
-
-#[macro_use]
-extern crate serde_derive;
-
-#[macro_use]
-extern crate clap;
-
-#[macro_use]
-extern crate lazy_static;
-
-use std::path::Path;
-use std::io::{self, Write};
-use std::process::Command;
 use std::env;
+use std::io::{self, Write};
 
-use tempfile::NamedTempFile;
-
-use rt_result::RtResult;
-use dependencies::dependency_tree;
-use tags::{update_tags, create_tags, move_tags};
-use config::Config;
-use types::SourceLock;
-
-#[macro_use]
-mod output;
+use log::LevelFilter;
 
-mod rt_result;
-mod dependencies;
-mod dirs;
-mod tags;
-mod types;
-mod config;
+use rusty_tags::{Action, RtResult, UpdateStatus, clean, print_completions, print_dependencies, run};
 
 fn main() {
-    execute().unwrap_or_else(|err| {
-        writeln!(&mut io::stderr(), "{}", err).unwrap();
-        std::process::exit(1);
-    });
-}
-
-fn execute() -> RtResult<()> {
-    let config = Config::from_command_args()?;
-    update_all_tags(&config)?;
-    Ok(())
-}
-
-fn update_all_tags(config: &Config) -> RtResult<()> {
-    let metadata = fetch_source_and_metadata(&config)?;
-    update_std_lib_tags(&config)?;
-
-    let mut source_locks = Vec::new();
-    let dep_tree = {
-        let mut dep_tree = dependency_tree(&config, &metadata)?;
-        let unlocked_root_ids: Vec<_> = {
-            let mut unlocked_roots = Vec::new();
-            for source in dep_tree.roots() {
-                match source.lock(&config.tags_spec)? {
-                    SourceLock::AlreadyLocked { ref path } => {
-                        info!(config, "Already creating tags for '{}', if this isn't the case remove the lock file '{}'",
-                              source.name, path.display());
-                        continue;
-                    }
-
-                    sl@SourceLock::Locked { .. } => {
-                        source_locks.push(sl);
-                        unlocked_roots.push(source);
-                    }
-                }
-            }
-
-            unlocked_roots.iter().map(|r| r.id).collect()
-        };
-
-        if unlocked_root_ids.is_empty() {
-            return Ok(());
+    init_logger();
+    match execute() {
+        Ok(status) => std::process::exit(status.exit_code()),
+        Err(err)   => {
+            writeln!(&mut io::stderr(), "{}", err).unwrap();
+            std::process::exit(1);
         }
-
-        dep_tree.set_roots(unlocked_root_ids);
-        dep_tree
-    };
-
-    update_tags(&config, &dep_tree)?;
-    Ok(())
+    }
 }
 
-fn fetch_source_and_metadata(config: &Config) -> RtResult<serde_json::Value> {
-    info!(config, "Fetching source and metadata ...");
-
-    env::set_current_dir(&config.start_dir)?;
-
-    let mut cmd = Command::new("cargo");
-    cmd.arg("metadata");
-    cmd.arg("--format-version=1");
-
-    let output = cmd.output()
-        .map_err(|err| format!("'cargo' execution failed: {}\nIs 'cargo' correctly installed?", err))?;
-
-    if ! output.status.success() {
-        let mut msg = String::from_utf8_lossy(&output.stderr).into_owned();
-        if msg.is_empty() {
-            msg = String::from_utf8_lossy(&output.stdout).into_owned();
+fn execute() -> RtResult<UpdateStatus> {
+    let action = Action::from_command_args()?;
+    if let Action::UpdateTags(ref config) = action {
+        if config.verbosity > 2 && env::var_os("RUST_LOG").is_none() {
+            log::set_max_level(LevelFilter::Debug);
         }
-
-        return Err(msg.into());
     }
 
-    Ok(serde_json::from_str(&String::from_utf8_lossy(&output.stdout))?)
-}
-
-fn update_std_lib_tags(config: &Config) -> RtResult<()> {
-    let src_path_str = env::var("RUST_SRC_PATH");
-    if ! src_path_str.is_ok() {
-        return Ok(());
-    }
+    match action {
+        Action::UpdateTags(config) => Ok(run(&config)?.status),
+        Action::Clean(config)      => {
+            clean(&config)?;
+            Ok(UpdateStatus::Complete)
+        }
 
-    let src_path_str = src_path_str.unwrap();
-    let src_path = Path::new(&src_path_str);
-    if ! src_path.is_dir() {
-        return Err(format!("Missing rust source code at '{}'!", src_path.display()).into());
-    }
+        Action::Deps(config)       => {
+            print_dependencies(&config)?;
+            Ok(UpdateStatus::Complete)
+        }
 
-    let std_lib_tags = src_path.join(config.tags_spec.file_name());
-    if std_lib_tags.is_file() && ! config.force_recreate {
-        return Ok(());
-    }
+        Action::Completions(shell) => {
+            print_completions(shell);
+            Ok(UpdateStatus::Complete)
+        }
 
-    let possible_src_dirs = [
-        "liballoc",
-        "libarena",
-        "libbacktrace",
-        "libcollections",
-        "libcore",
-        "libflate",
-        "libfmt_macros",
-        "libgetopts",
-        "libgraphviz",
-        "liblog",
-        "librand",
-        "librbml",
-        "libserialize",
-        "libstd",
-        "libsyntax",
-        "libterm"
-    ];
+        Action::PrintPaths(paths)  => {
+            for path in &paths {
+                println!("{}", path.display());
+            }
 
-    let mut src_dirs = Vec::new();
-    for dir in &possible_src_dirs {
-        let src_dir = src_path.join(&dir);
-        if src_dir.is_dir() {
-            src_dirs.push(src_dir);
+            Ok(UpdateStatus::Complete)
         }
     }
+}
 
-    info!(config, "Creating tags for the standard library ...");
-
-    let tmp_std_lib_tags = NamedTempFile::new_in(&src_path)?;
-    create_tags(config, &src_dirs, tmp_std_lib_tags.path())?;
-    move_tags(config, tmp_std_lib_tags.path(), &std_lib_tags)?;
+/// Initializes the logging of the debug traces, which is controlled by 'RUST_LOG'.
+/// Without it only warnings are logged till '--verbose' is given three times, which
+/// isn't known before parsing the arguments.
+fn init_logger() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("rusty_tags=debug"))
+        .format_timestamp_millis()
+        .init();
 
-    Ok(())
+    if env::var_os("RUST_LOG").is_none() {
+        log::set_max_level(LevelFilter::Warn);
+    }
 }
@@ -1,15 +1,26 @@
-#[macro_use]
+/// Prints to stdout, or to stderr if `$to_stderr` is true, which is
+/// the case if the tags or the summary are written to stdout and
+/// therefore any other output would corrupt them.
+macro_rules! output {
+    ($to_stderr:expr, $($arg:tt)*) => {{
+        if $to_stderr {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    }};
+}
 
 macro_rules! info {
     ($config:ident, $fmt:expr) => {{
         if ! $config.quiet {
-            println!($fmt);
+            output!($config.output_to_stderr, $fmt);
         }
     }};
 
     ($config:ident, $fmt:expr, $($arg:tt)*) => {{
         if ! $config.quiet {
-            println!($fmt, $($arg)*);
+            output!($config.output_to_stderr, $fmt, $($arg)*);
         }
     }};
 }
@@ -17,13 +28,29 @@ macro_rules! info {
 macro_rules! verbose {
     ($config:ident, $fmt:expr) => {{
         if $config.verbose {
-            println!($fmt);
+            output!($config.output_to_stderr, $fmt);
         }
     }};
 
     ($config:ident, $fmt:expr, $($arg:tt)*) => {{
         if $config.verbose {
-            println!($fmt, $($arg)*);
+            output!($config.output_to_stderr, $fmt, $($arg)*);
+        }
+    }};
+}
+
+/// Warnings are written to stderr and are also shown with '--quiet',
+/// they're only suppressed by '--no-warnings'.
+macro_rules! warn {
+    ($config:ident, $fmt:expr) => {{
+        if ! $config.no_warnings {
+            eprintln!($fmt);
+        }
+    }};
+
+    ($config:ident, $fmt:expr, $($arg:tt)*) => {{
+        if ! $config.no_warnings {
+            eprintln!($fmt, $($arg)*);
         }
     }};
 }
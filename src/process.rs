@@ -0,0 +1,61 @@
+use std::io::{self, Read};
+use std::process::{Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::debug;
+
+/// The interval in which a child process is checked for its termination.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Executes 'cmd' like 'Command::output', but kills it if it's still running
+/// after 'timeout', then 'None' is returned.
+pub fn output_with_timeout(cmd: &mut Command, timeout: Option<Duration>) -> io::Result<Option<Output>> {
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None          => return cmd.output().map(Some)
+    };
+
+    let mut child = cmd.stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // the pipes have to be read while waiting, otherwise
+    // the child might block on a full pipe buffer
+    let stdout = child.stdout.take().map(read_in_thread);
+    let stderr = child.stderr.take().map(read_in_thread);
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+
+        if Instant::now() >= deadline {
+            debug!("Killing {:?} after {:.2?}", cmd, timeout);
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(None);
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    };
+
+    let join = |reader: Option<thread::JoinHandle<io::Result<Vec<u8>>>>| -> io::Result<Vec<u8>> {
+        match reader {
+            Some(reader) => reader.join().unwrap_or_else(|_| Ok(Vec::new())),
+            None         => Ok(Vec::new())
+        }
+    };
+
+    Ok(Some(Output { status, stdout: join(stdout)?, stderr: join(stderr)? }))
+}
+
+fn read_in_thread<R: Read + Send + 'static>(mut reader: R) -> thread::JoinHandle<io::Result<Vec<u8>>> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    })
+}
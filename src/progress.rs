@@ -0,0 +1,88 @@
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use config::Config;
+use types::Source;
+use color::Style;
+
+/// Reports the progress of the tags creation of the sources to stderr. On a
+/// terminal as a single updating line, otherwise as a line per source.
+pub struct Progress {
+    /// the number of sources which tags are created
+    num_sources: usize,
+
+    /// the number of sources which tags creation already started
+    num_started: AtomicUsize,
+
+    /// if the progress is shown as a single updating line
+    single_line: bool,
+
+    quiet: bool,
+
+    colored: bool,
+
+    /// the start of the update of the tags
+    start: Instant,
+
+    /// serializes the output of the threads creating the tags
+    output_lock: Mutex<()>
+}
+
+impl Progress {
+    pub fn new(config: &Config, num_sources: usize, start: Instant) -> Progress {
+        Progress {
+            num_sources,
+            num_started: AtomicUsize::new(0),
+            // the verbose output would be mixed into the updated line
+            single_line: io::stderr().is_terminal() && ! config.verbose,
+            quiet: config.quiet,
+            colored: config.colored,
+            start,
+            output_lock: Mutex::new(())
+        }
+    }
+
+    /// Reports that the tags creation of 'source' started.
+    pub fn start_source(&self, source: &Source) {
+        if self.quiet {
+            return;
+        }
+
+        let num = self.num_started.fetch_add(1, Ordering::SeqCst) + 1;
+        let _lock = self.output_lock.lock();
+        let msg = format!("[{}/{}] tagging {} {}", num, self.num_sources,
+                          Style::Name.paint(self.colored, &source.name), source.version);
+        if self.single_line {
+            eprint!("\r\x1b[K{}", msg);
+            let _ = io::stderr().flush();
+        } else {
+            eprintln!("{}", msg);
+        }
+    }
+
+    /// Ends the updating line, which has to be done before any other output.
+    pub fn end_line(&self) {
+        if ! self.quiet && self.single_line && self.num_started.load(Ordering::SeqCst) > 0 {
+            eprintln!();
+        }
+    }
+
+    /// Prints the final tally of the tags creation with the elapsed time.
+    pub fn finish(&self, num_created: usize, num_up_to_date: usize, num_missing: usize, num_failed: usize) {
+        if self.quiet {
+            return;
+        }
+
+        let missing = format!("{} missing", num_missing);
+        let mut msg = format!("Created tags for {} sources, {}, {}", num_created,
+                              Style::Skipped.paint(self.colored, format!("skipped {} up-to-date", num_up_to_date)),
+                              if num_missing > 0 { Style::Missing.paint(self.colored, missing) } else { missing });
+        if num_failed > 0 {
+            msg.push_str(&format!(", {}", Style::Error.paint(self.colored, format!("{} failed", num_failed))));
+        }
+
+        eprintln!("{}, in {:.2?}", msg, self.start.elapsed());
+    }
+}
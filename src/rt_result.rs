@@ -1,11 +1,17 @@
 use std::io;
+use std::error::Error;
+use std::path::PathBuf;
+use std::process::ExitStatus;
 use std::convert::From;
 use std::fmt::{self, Display, Formatter};
+use std::sync::Arc;
 
 use toml;
 use serde_json;
 use semver::{ReqParseError, SemVerError};
 
+use types::TagsKind;
+
 /// The result used in the whole application.
 pub type RtResult<T> = Result<T, RtErr>;
 
@@ -14,31 +20,152 @@ pub type RtResult<T> = Result<T, RtErr>;
 pub enum RtErr {
     /// generic error message
     Message(String),
+
+    /// an io error, shared because the error of the rusty-tags
+    /// directories is cached and 'io::Error' isn't cloneable
+    Io(Arc<io::Error>),
+
+    /// the toml of the config file 'file' - if it's known - couldn't be parsed
+    TomlParse {
+        file: Option<PathBuf>,
+        error: toml::de::Error
+    },
+
+    /// the json output of 'cargo metadata' couldn't be parsed or
+    /// the summary couldn't be written as json
+    Json(Arc<serde_json::Error>),
+
+    /// 'file_name' couldn't be found upwards from 'start_dir'
+    NotFound {
+        file_name: String,
+        start_dir: PathBuf
+    },
+
+    /// the source of the dependency 'name' - the name or 'name@version'
+    /// of the crate - couldn't be found
+    MissingSource {
+        name: String
+    },
+
+    /// no ctags executable could be found under the
+    /// names 'exes' for the creation of tags of 'kind'
+    MissingTagsExe {
+        exes: Vec<String>,
+        kind: TagsKind
+    },
+
+    /// the source of the rust standard library
+    /// couldn't be found in any of 'searched_dirs'
+    MissingStdLibSrc {
+        searched_dirs: Vec<PathBuf>
+    },
+
+    /// the execution of 'command' for 'target' - the sources or the cargo
+    /// project - didn't finish in 'secs' seconds and was killed
+    Timeout {
+        command: String,
+        target: String,
+        secs: u64
+    },
+
+    /// the execution of 'command' for 'target' - the sources or the cargo
+    /// project - failed with 'status' and the output 'stderr'
+    CommandFailed {
+        command: String,
+        target: String,
+        status: ExitStatus,
+        stderr: String
+    }
 }
 
 impl Display for RtErr {
     fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
         match self {
-            &RtErr::Message(ref msg) => writeln!(f, "{}", msg),
+            RtErr::Message(msg) => writeln!(f, "{}", msg),
+
+            RtErr::Io(err) => writeln!(f, "{}", err),
+
+            RtErr::TomlParse { file: Some(file), error } => writeln!(f, "Invalid config file '{}': {}", file.display(), error),
+
+            RtErr::TomlParse { file: None, error } => writeln!(f, "{}", error),
+
+            RtErr::Json(err) => writeln!(f, "{}", err),
+
+            RtErr::NotFound { file_name, start_dir } => {
+                writeln!(f, "Couldn't find '{}' starting at directory '{}'!", file_name, start_dir.display())
+            }
+
+            RtErr::MissingSource { name } => {
+                writeln!(f, "Couldn't find the source of the dependency '{}'! Without '--no-fetch' cargo downloads the sources of the registry packages.", name)
+            }
+
+            RtErr::MissingTagsExe { exes, kind: TagsKind::Gtags } => {
+                writeln!(f, "Couldn't find 'gtags' executable for the creation of gtags! Searched for executables with names: {:?}.", exes)?;
+                writeln!(f, "Is GNU Global correctly installed? It can be e.g. installed with 'apt install global' or 'brew install global'.")?;
+                writeln!(f, "A gtags executable with a different name or location can be set by '--gtags-exe'.")
+            }
+
+            RtErr::MissingTagsExe { exes, kind: TagsKind::Cscope } => {
+                writeln!(f, "Couldn't find 'cscope' executable for the creation of the cscope database! Searched for executables with names: {:?}.", exes)?;
+                writeln!(f, "Is 'cscope' correctly installed? It can be e.g. installed with 'apt install cscope' or 'brew install cscope'.")?;
+                writeln!(f, "A cscope executable with a different name or location can be set by '--cscope-exe'.")
+            }
+
+            RtErr::MissingTagsExe { exes, kind } => {
+                writeln!(f, "Couldn't find 'ctags' executable for the creation of {} tags! Searched for executables with names: {:?}.",
+                         kind, exes)?;
+                writeln!(f, "Is 'ctags' correctly installed? It can be e.g. installed with 'apt install universal-ctags' or 'brew install universal-ctags'.")?;
+                writeln!(f, "A ctags executable with a different name or location can be set by '--ctags-exe' or by 'ctags_exe' in '~/.rusty-tags/config.toml'.")
+            }
+
+            RtErr::MissingStdLibSrc { searched_dirs } => {
+                writeln!(f, "Couldn't find the source of the rust standard library! Searched in the directories: {:?}.", searched_dirs)?;
+                writeln!(f, "It can be installed with 'rustup component add rust-src' or its location can be set by the environment variable 'RUST_SRC_PATH'.")
+            }
+
+            RtErr::Timeout { command, target, secs } => {
+                writeln!(f, "'{}' for {} didn't finish in {} seconds and was killed! The time limit can be changed by '--timeout'.",
+                         command, target, secs)
+            }
+
+            RtErr::CommandFailed { command, target, status, stderr } => {
+                let stderr = stderr.trim_end();
+                if stderr.is_empty() {
+                    writeln!(f, "'{}' for {} failed ({}) without any output!", command, target, status)
+                } else {
+                    writeln!(f, "'{}' for {} failed ({}):\n{}", command, target, status, stderr)
+                }
+            }
+        }
+    }
+}
+
+impl Error for RtErr {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RtErr::Io(err)                 => Some(&**err),
+            RtErr::TomlParse { error, .. } => Some(error),
+            RtErr::Json(err)               => Some(&**err),
+            _                              => None
         }
     }
 }
 
 impl From<io::Error> for RtErr {
     fn from(err: io::Error) -> RtErr {
-        RtErr::Message(format!("{}", err))
+        RtErr::Io(Arc::new(err))
     }
 }
 
 impl From<toml::de::Error> for RtErr {
-    fn from(err: toml::de::Error) -> RtErr {
-        RtErr::Message(err.to_string())
+    fn from(error: toml::de::Error) -> RtErr {
+        RtErr::TomlParse { file: None, error }
     }
 }
 
 impl From<serde_json::Error> for RtErr {
     fn from(err: serde_json::Error) -> RtErr {
-        RtErr::Message(format!("{}", err))
+        RtErr::Json(Arc::new(err))
     }
 }
 
@@ -48,7 +175,7 @@ impl From<String> for RtErr {
     }
 }
 
-impl<'a> From<&'a str> for RtErr {
+impl From<&str> for RtErr {
     fn from(s: &str) -> RtErr {
         RtErr::Message(s.to_owned())
     }
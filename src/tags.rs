@@ -1,25 +1,49 @@
-use std::fs::{File, OpenOptions, copy, rename};
-use std::io::{Read, Write, BufWriter};
-use std::path::Path;
+use std::fs::{self, File, OpenOptions, copy, rename};
+use std::io::{self, Read, Write, BufRead, BufReader, BufWriter};
+use std::collections::BinaryHeap;
+use std::cmp::{Ordering, Reverse};
+use std::path::{self, Component, Path, PathBuf};
+use std::env;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use std::process::{Command, Output};
+use std::sync::Mutex;
+use std::borrow::Cow;
+use std::hash::Hasher;
+#[cfg(unix)]
+use std::ffi::OsStr;
+#[cfg(unix)]
+use std::os::unix::ffi::OsStrExt;
 
 use tempfile::NamedTempFile;
 use scoped_threadpool::Pool;
-use fnv::FnvHashSet;
+use fnv::{FnvHashSet, FnvHasher};
 
-use rt_result::RtResult;
-use types::{TagsKind, SourceWithTmpTags, Sources, DepTree, unique_sources};
+use rt_result::{RtResult, RtErr};
+use types::{TagsKind, TagsSort, Source, SourceId, SourceWithTmpTags, DepTree, TagsSummary, RootSummary, FailureSummary, unique_sources,
+            rust_files, find_dir_upwards_containing, ctags_command_hash, CSCOPE_FILES_LIST_NAME};
 use config::Config;
-use dirs::rusty_tags_cache_dir;
+use dirs::{rusty_tags_cache_dir, rust_std_lib_tags_file};
+use process::output_with_timeout;
+use progress::Progress;
+use color::Style;
+use log::debug;
 
 /// Update the tags of all sources in 'dep_tree'
-pub fn update_tags(config: &Config, dep_tree: &DepTree) -> RtResult<()> {
+pub fn update_tags(config: &Config, dep_tree: &DepTree) -> RtResult<TagsSummary> {
+    let start = Instant::now();
     if ! config.quiet {
         let names: Vec<_> = dep_tree.roots().map(|r| &r.name).collect();
-        println!("Creating tags for: {:?} ...", names);
+        output!(config.output_to_stderr, "Creating {} tags for: {:?} ...", config.tags_spec.file_extension(), names);
+    }
+
+    match config.tags_spec.kind {
+        TagsKind::Gtags  => return update_gtags(config, dep_tree),
+        TagsKind::Cscope => return update_cscope(config, dep_tree),
+        _                => {}
     }
 
     let sources_to_update: Vec<_> = dep_tree.all_sources().filter(|s| {
-        s.needs_tags_update(config)
+        s.needs_tags_update(config, dep_tree)
     })
     .collect();
 
@@ -27,23 +51,61 @@ pub fn update_tags(config: &Config, dep_tree: &DepTree) -> RtResult<()> {
     // dependent (ancestor) sources also have to be updated. The reason for the missing tags
     // might be a version change of the source - by changes in the 'Cargo.toml' - so all
     // dependent sources have to be rebuild to include the new version.
-    let sources_to_update = {
+    let mut vanished_ids = Vec::new();
+    let mut sources_to_update = {
         let mut srcs = dep_tree.ancestors(&sources_to_update);
         srcs.extend(&sources_to_update);
         unique_sources(&mut srcs);
 
+        // the source directory of a dependency might have vanished - or its extraction
+        // by cargo isn't complete yet - since the dependency tree was built, then the
+        // dependency is reported like a source which couldn't be found
+        srcs.retain(|src| {
+            if src.is_root || src.dir.is_dir() {
+                return true;
+            }
+
+            warn!(config, "Couldn't find the source directory '{}' of {}, so it's missing in the tags",
+                  src.dir.display(), Style::Missing.paint(config.colored, src.source_version()));
+            vanished_ids.push(src.id);
+            false
+        });
+
+        if config.dry_run {
+            print_dry_run(config, dep_tree, &srcs);
+            return Ok(tags_summary(config, dep_tree, Vec::new()));
+        }
+
         let mut srcs_with_tags = Vec::with_capacity(srcs.len());
         for src in &srcs {
-            srcs_with_tags.push(SourceWithTmpTags::new(src)?);
+            if config.force_recreate {
+                src.remove_files_hash()?;
+            }
+
+            srcs_with_tags.push(SourceWithTmpTags::new(config, dep_tree, src)?);
         }
 
         srcs_with_tags
     };
 
+    let up_to_date: Vec<String> = {
+        let updated_ids: FnvHashSet<SourceId> = sources_to_update.iter().map(|s| s.source.id).chain(vanished_ids.iter().cloned()).collect();
+        let mut srcs: Vec<_> = dep_tree.all_sources().filter(|s| ! updated_ids.contains(&s.id)).collect();
+        srcs.sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+        srcs.iter().map(|s| s.source_version()).collect()
+    };
+
+    if config.verbose && ! up_to_date.is_empty() {
+        output!(config.output_to_stderr, "\nSkipping up-to-date sources:");
+        for source_version in &up_to_date {
+            output!(config.output_to_stderr, "   {}", Style::Skipped.paint(config.colored, source_version));
+        }
+    }
+
     if config.verbose && ! sources_to_update.is_empty() {
-        println!("\nCreating tags for sources:");
+        output!(config.output_to_stderr, "\nCreating tags for sources:");
         for &SourceWithTmpTags { source, .. } in &sources_to_update {
-            println!("   {}", source.recreate_status(config));
+            output!(config.output_to_stderr, "   {}", source.recreate_status(config));
         }
     }
 
@@ -53,20 +115,45 @@ pub fn update_tags(config: &Config, dep_tree: &DepTree) -> RtResult<()> {
         None
     };
 
+    // the sources which tags creation failed while 'keep_going'
+    let mut failures = Vec::new();
+
+    let num_up_to_date = up_to_date.len();
+    let progress = Progress::new(config, sources_to_update.len(), start);
+
     // Create the tags for each source in 'sources_to_update'. This creates
     // only the tags of the source without considering the dependencies.
     if let Some(ref mut thread_pool) = thread_pool {
+        let errors = Mutex::new(Vec::new());
         thread_pool.scoped(|scoped| {
-            for &SourceWithTmpTags { ref source, ref tags_file } in &sources_to_update {
+            for &SourceWithTmpTags { source, ref tags_file, .. } in &sources_to_update {
+                let errors = &errors;
+                let progress = &progress;
                 scoped.execute(move || {
-                    create_tags(config, &[&source.dir], tags_file.path()).unwrap();
+                    progress.start_source(source);
+                    if let Err(err) = create_tags(config, &source.src_dirs(config), tags_file.path()) {
+                        errors.lock().unwrap().push((source.id, err));
+                    }
                 });
             }
         });
+
+        progress.end_line();
+        remove_failed_sources(config, into_errors(errors), &mut sources_to_update, &mut failures)?;
     } else {
-        for &SourceWithTmpTags { ref source, ref tags_file } in &sources_to_update {
-            create_tags(config, &[&source.dir], tags_file.path())?;
+        let mut errors = Vec::new();
+        for &SourceWithTmpTags { source, ref tags_file, .. } in &sources_to_update {
+            progress.start_source(source);
+            if let Err(err) = create_tags(config, &source.src_dirs(config), tags_file.path()) {
+                errors.push((source.id, err));
+                if ! config.keep_going {
+                    break;
+                }
+            }
         }
+
+        progress.end_line();
+        remove_failed_sources(config, errors, &mut sources_to_update, &mut failures)?;
     }
 
     // Creates the cachable tags of each source in 'sources_to_update'. The cachable
@@ -74,25 +161,214 @@ pub fn update_tags(config: &Config, dep_tree: &DepTree) -> RtResult<()> {
     // Furthermore creates the final tags of each source in 'sources_to_update'. The
     // final tags contain
     //
+    // The cached tags of the dependencies are merged into the tags of a source,
+    // so the sources are updated in waves, each after the one of its dependencies.
+    let waves = update_waves(dep_tree, &sources_to_update);
     if let Some(ref mut thread_pool) = thread_pool {
-        thread_pool.scoped(|scoped| {
-            for src in &sources_to_update {
-                scoped.execute(move || {
-                    let deps = dep_tree.dependencies(src.source);
-                    update_tags_internal(config, src, deps).unwrap();
-                });
-            }
-        });
+        let errors = Mutex::new(Vec::new());
+        for wave in &waves {
+            thread_pool.scoped(|scoped| {
+                for &idx in wave {
+                    let src = &sources_to_update[idx];
+                    let errors = &errors;
+                    scoped.execute(move || {
+                        let deps = sorted_dependencies(dep_tree, src.source);
+                        if let Err(err) = update_tags_internal(config, dep_tree, src, &deps) {
+                            errors.lock().unwrap().push((src.source.id, err));
+                        }
+                    });
+                }
+            });
+        }
+
+        remove_failed_sources(config, into_errors(errors), &mut sources_to_update, &mut failures)?;
     } else {
-        for src in &sources_to_update {
-            let deps = dep_tree.dependencies(src.source);
-            update_tags_internal(config, src, deps)?;
+        let mut errors = Vec::new();
+        for &idx in waves.iter().flatten() {
+            let src = &sources_to_update[idx];
+            let deps = sorted_dependencies(dep_tree, src.source);
+            if let Err(err) = update_tags_internal(config, dep_tree, src, &deps) {
+                errors.push((src.source.id, err));
+                if ! config.keep_going {
+                    break;
+                }
+            }
+        }
+
+        remove_failed_sources(config, errors, &mut sources_to_update, &mut failures)?;
+    }
+
+    if let Some(workspace_tags_file) = dep_tree.workspace_tags_file() {
+        update_workspace_tags(config, dep_tree, workspace_tags_file)?;
+    }
+
+    let num_missing_sources = dep_tree.num_missing_sources() + vanished_ids.len();
+    progress.finish(sources_to_update.len(), num_up_to_date, num_missing_sources, failures.len());
+    let mut summary = tags_summary(config, dep_tree, failures);
+    summary.up_to_date = up_to_date;
+    summary.missing_sources = num_missing_sources;
+    if config.no_merge {
+        summary.tag_files = separate_tag_files(config, dep_tree, &vanished_ids);
+        if ! config.summary_json {
+            for file in &summary.tag_files {
+                println!("{}", file.display());
+            }
+        }
+    }
+
+    return Ok(summary);
+
+    /// The dependencies of 'source' sorted by their name and version,
+    /// to get a reproducible order of the merged tags files.
+    fn sorted_dependencies<'a>(dep_tree: &'a DepTree, source: &Source) -> Vec<&'a Source> {
+        let mut deps: Vec<_> = dep_tree.dependencies(source).collect();
+        deps.sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+        deps
+    }
+
+    /// Prints what would be done for 'sources' without creating any tags.
+    fn print_dry_run(config: &Config, dep_tree: &DepTree, sources: &[&Source]) {
+        let to_stderr = config.output_to_stderr;
+        if sources.is_empty() {
+            output!(to_stderr, "\nAll {} tags are up to date", config.tags_spec.file_extension());
+            return;
+        }
+
+        output!(to_stderr, "\nWould create tags for sources:");
+        for source in sources {
+            output!(to_stderr, "   {}", source.recreate_status(config));
+            output!(to_stderr, "      source dir: {}", source.dir.display());
+            output!(to_stderr, "      cached tags: {}", source.cached_tags_file.display());
+            if source.is_root && ! config.workspace_tags {
+                if config.tags_to_stdout {
+                    output!(to_stderr, "      tags: <stdout>");
+                } else {
+                    output!(to_stderr, "      tags: {}", source.tags_file.display());
+                }
+
+                let deps: Vec<_> = sorted_dependencies(dep_tree, source).iter()
+                    .map(|d| d.source_version())
+                    .collect();
+
+                if ! deps.is_empty() {
+                    output!(to_stderr, "      merged with: {:?}", deps);
+                }
+            }
+        }
+
+        if let Some(workspace_tags_file) = dep_tree.workspace_tags_file() {
+            if config.tags_to_stdout {
+                output!(to_stderr, "\nWould write the tags of the workspace to: <stdout>");
+            } else {
+                output!(to_stderr, "\nWould write the tags of the workspace to: {}", workspace_tags_file.display());
+            }
+        }
+    }
+
+    /// Merges the cached tags of all roots and of their dependencies into 'workspace_tags_file'.
+    fn update_workspace_tags(config: &Config, dep_tree: &DepTree, workspace_tags_file: &Path) -> RtResult<()> {
+        let mut sources: Vec<&Source> = dep_tree.roots().collect();
+        for root in dep_tree.roots() {
+            sources.extend(sorted_dependencies(dep_tree, root));
+        }
+
+        unique_sources(&mut sources);
+
+        let mut tags_files: Vec<&Path> = sources.iter()
+            .filter_map(|s| {
+                if s.cached_tags_file.is_file() {
+                    Some(s.cached_tags_file.as_path())
+                } else {
+                    verbose!(config, "\nCouldn't find tags file '{}' of workspace source. Might be a cyclic dependency?",
+                             s.cached_tags_file.display());
+                    None
+                }
+            })
+            .collect();
+
+        tags_files.extend(config.extra_tags_files.iter().map(PathBuf::as_path));
+
+        let (first_tags_file, other_tags_files) = match tags_files.split_first() {
+            Some(files) => files,
+            None        => return Ok(())
+        };
+
+        if config.tags_to_stdout {
+            return write_merged_tags_to_stdout(config, first_tags_file, other_tags_files);
+        }
+
+        let merged = update_root_tags(config, dep_tree, workspace_tags_file, first_tags_file, other_tags_files)?;
+        check_root_tags(config, "the workspace", workspace_tags_file)?;
+        if merged {
+            run_post_hook(config, None, workspace_tags_file)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the errors collected by the threads of the pool.
+    fn into_errors(errors: Mutex<Vec<(SourceId, RtErr)>>) -> Vec<(SourceId, RtErr)> {
+        errors.into_inner().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Without '--keep-going' the first of the 'errors' is returned, otherwise the
+    /// failed sources are removed from 'sources' and collected in 'failures'.
+    fn remove_failed_sources(config: &Config,
+                             errors: Vec<(SourceId, RtErr)>,
+                             sources: &mut Vec<SourceWithTmpTags>,
+                             failures: &mut Vec<(String, RtErr)>)
+                             -> RtResult<()> {
+        if ! config.keep_going {
+            return match errors.into_iter().next() {
+                Some((_, err)) => Err(err),
+                None           => Ok(())
+            };
+        }
+
+        for (id, err) in errors {
+            if let Some(idx) = sources.iter().position(|s| s.source.id == id) {
+                let source = sources.remove(idx).source;
+                warn!(config, "{}, continuing because of '--keep-going'",
+                      Style::Error.paint(config.colored, format!("Couldn't create the tags of {}", source.source_version())));
+                failures.push((source.source_version(), err));
+            }
         }
+
+        Ok(())
     }
 
-    return Ok(());
+    /// Groups the indices of 'sources' into waves, so that the dependencies of a
+    /// source, which are also updated, are in earlier waves. The sources of a
+    /// dependency cycle can't be ordered and end up in the same wave.
+    fn update_waves(dep_tree: &DepTree, sources: &[SourceWithTmpTags]) -> Vec<Vec<usize>> {
+        let updated_ids: FnvHashSet<SourceId> = sources.iter().map(|s| s.source.id).collect();
+        let mut done_ids = FnvHashSet::default();
+        let mut remaining: Vec<usize> = (0..sources.len()).collect();
+        let mut waves = Vec::new();
+        while ! remaining.is_empty() {
+            let (mut wave, blocked): (Vec<usize>, Vec<usize>) = remaining.iter().partition(|&&idx| {
+                let source = sources[idx].source;
+                dep_tree.dependencies(source)
+                    .all(|d| d.id == source.id || ! updated_ids.contains(&d.id) || done_ids.contains(&d.id))
+            });
+
+            remaining = blocked;
+            if wave.is_empty() {
+                wave = std::mem::take(&mut remaining);
+            }
+
+            done_ids.extend(wave.iter().map(|&idx| sources[idx].source.id));
+            waves.push(wave);
+        }
+
+        waves
+    }
 
-    fn update_tags_internal<'a>(config: &Config, source_with_tags: &SourceWithTmpTags<'a>, dependencies: Sources<'a>) -> RtResult<()> {
+    fn update_tags_internal<'a>(config: &Config,
+                                dep_tree: &DepTree,
+                                source_with_tags: &SourceWithTmpTags<'a>,
+                                dependencies: &[&'a Source])
+                                -> RtResult<()> {
         let source = source_with_tags.source;
         let tmp_src_tags = source_with_tags.tags_file.path();
 
@@ -103,17 +379,17 @@ pub fn update_tags(config: &Config, dep_tree: &DepTree) -> RtResult<()> {
             let reexported_crates = find_reexported_crates(&source.dir)?;
 
             if ! reexported_crates.is_empty() && config.verbose {
-                println!("\nFound public reexports in '{}' of:", source.name);
+                output!(config.output_to_stderr, "\nFound public reexports in '{}' of:", source.name);
                 for rcrate in &reexported_crates {
-                    println!("   {}", rcrate);
+                    output!(config.output_to_stderr, "   {}", rcrate);
                 }
 
-                println!("");
+                output!(config.output_to_stderr, "");
             }
 
             // collect the tags files of reexported dependencies
-            let reexported_tags_files: Vec<&Path> = dependencies.clone()
-                .filter(|d| reexported_crates.iter().find(|c| **c == d.name) != None)
+            let reexported_tags_files: Vec<&Path> = dependencies.iter()
+                .filter(|d| reexported_crates.iter().find(|c| **c == d.name).is_some())
                 .filter_map(|d| {
                     if d.cached_tags_file.is_file() {
                         Some(d.cached_tags_file.as_path())
@@ -125,7 +401,7 @@ pub fn update_tags(config: &Config, dep_tree: &DepTree) -> RtResult<()> {
                 })
                 .collect();
 
-            let tmp_cached_tags = NamedTempFile::new_in(rusty_tags_cache_dir()?)?;
+            let tmp_cached_tags = NamedTempFile::new_in(config.artifacts_dir()?)?;
             if ! reexported_tags_files.is_empty() {
                 merge_tags(config, tmp_src_tags, &reexported_tags_files, tmp_cached_tags.path())?;
             } else {
@@ -135,10 +411,15 @@ pub fn update_tags(config: &Config, dep_tree: &DepTree) -> RtResult<()> {
             move_tags(config, tmp_cached_tags.path(), &source.cached_tags_file)?;
         }
 
+        // with '--no-merge' the cached tags file is the tags file of 'source'
+        if config.no_merge {
+            return store_files_hash(source_with_tags);
+        }
+
         // create the source tags file of 'source' by merging
         // the tags of 'source' and of its dependencies
         {
-            let dep_tags_files: Vec<&Path> = dependencies.clone()
+            let mut dep_tags_files: Vec<&Path> = dependencies.iter()
                 .filter_map(|d| {
                     if d.cached_tags_file.is_file() {
                         Some(d.cached_tags_file.as_path())
@@ -150,220 +431,1374 @@ pub fn update_tags(config: &Config, dep_tree: &DepTree) -> RtResult<()> {
                 })
                 .collect();
 
-            let tmp_src_and_dep_tags = NamedTempFile::new_in(&source.dir)?;
-            if ! dep_tags_files.is_empty() {
-                merge_tags(config, tmp_src_tags, &dep_tags_files, tmp_src_and_dep_tags.path())?;
-            } else {
-                copy_tags(config, tmp_src_tags, tmp_src_and_dep_tags.path())?;
+            if source.is_root {
+                dep_tags_files.extend(config.extra_tags_files.iter().map(PathBuf::as_path));
+            }
+
+            // the tags of the roots are merged into the workspace tags file afterwards
+            if source.is_root && config.workspace_tags {
+                return store_files_hash(source_with_tags);
+            }
+
+            if source.is_root && config.tags_to_stdout {
+                return write_merged_tags_to_stdout(config, tmp_src_tags, &dep_tags_files);
+            }
+
+            // with '--single-file' the dependencies don't get tags files in their source directories
+            if ! source.is_root && config.single_file.is_some() {
+                return store_files_hash(source_with_tags);
             }
 
-            move_tags(config, tmp_src_and_dep_tags.path(), &source.tags_file)?;
+            if source.is_root {
+                let merged = update_root_tags(config, dep_tree, &source.tags_file, tmp_src_tags, &dep_tags_files)?;
+                check_root_tags(config, &source.source_version(), &source.tags_file)?;
+                if merged {
+                    run_post_hook(config, Some(&source.name), &source.tags_file)?;
+                }
+            } else {
+                // the temporary file has to be on the same file system as the final
+                // tags file, otherwise the moving of the tags file wouldn't be atomic
+                let tags_dir = source.tags_file.parent().unwrap_or(&source.dir);
+                let tmp_src_and_dep_tags = NamedTempFile::new_in(tags_dir)?;
+                merge_keeping_foreign_tags(config, dep_tree, None, tmp_src_tags, &dep_tags_files, tmp_src_and_dep_tags.path())?;
+                move_tags(config, tmp_src_and_dep_tags.path(), &source.tags_file)?;
+            }
         }
 
-        Ok(())
+        store_files_hash(source_with_tags)
     }
-}
 
-/// creates tags recursive for the directory hierarchies starting at `src_dirs`
-/// and writes them to `tags_file`
-pub fn create_tags<P1, P2>(config: &Config, src_dirs: &[P1], tags_file: P2) -> RtResult<()>
-    where P1: AsRef<Path>,
-          P2: AsRef<Path>
-{
-    let mut cmd = config.tags_spec.ctags_command();
-    cmd.arg("-o")
-       .arg(tags_file.as_ref());
+    fn store_files_hash(source_with_tags: &SourceWithTmpTags) -> RtResult<()> {
+        match source_with_tags.files_hash {
+            Some(ref hash) => source_with_tags.source.store_files_hash(hash),
+            None           => Ok(())
+        }
+    }
+}
 
-    for dir in src_dirs {
-        cmd.arg(dir.as_ref());
+/// The tags files which would be merged into the tags of the roots, listed by '--no-merge':
+/// the tags of the standard library, the cached tags of every source - sorted by their name
+/// and version - and the extra tags files. The sources of 'vanished_ids' are missing.
+fn separate_tag_files(config: &Config, dep_tree: &DepTree, vanished_ids: &[SourceId]) -> Vec<PathBuf> {
+    let mut tag_files = Vec::new();
+    if config.std_lib_tags {
+        if let Ok(std_lib_tags) = rust_std_lib_tags_file(config.tags_spec.file_extension()) {
+            if std_lib_tags.is_file() {
+                tag_files.push(std_lib_tags);
+            }
+        }
     }
 
-    if config.verbose {
-        println!("\nCreating tags ...\n   with command: {:?}", cmd);
+    let mut sources: Vec<_> = dep_tree.all_sources()
+        .filter(|s| ! vanished_ids.contains(&s.id) && s.cached_tags_file.is_file())
+        .collect();
 
-        println!("\n   for source:");
-        for dir in src_dirs {
-            println!("      {}", dir.as_ref().display());
+    sources.sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+    tag_files.extend(sources.iter().map(|s| s.cached_tags_file.clone()));
+    tag_files.extend(config.extra_tags_files.iter().cloned());
+    tag_files
+}
+
+/// Merges the tags of `lib_tag_file` and `dependency_tag_files` into the tags file
+/// `tags_file` of a root or of the workspace. The hash of the merged tags files is
+/// remembered in the cache directory, and if neither they nor `tags_file` changed
+/// since the last run, the merge is skipped. Returns if `tags_file` was written.
+fn update_root_tags(config: &Config,
+                    dep_tree: &DepTree,
+                    tags_file: &Path,
+                    lib_tag_file: &Path,
+                    dependency_tag_files: &[&Path])
+                    -> RtResult<bool> {
+    let record_file = merge_record_file(config, tags_file)?;
+    let inputs_hash = merge_inputs_hash(config, lib_tag_file, dependency_tag_files)?;
+    if ! config.force_recreate {
+        if let (Ok(record), Some(stamp)) = (fs::read_to_string(&record_file), tags_file_stamp(tags_file)) {
+            if record == format!("{}\n{}", inputs_hash, stamp) {
+                verbose!(config, "\nSkipping the merge of the unchanged tags of '{}'", tags_file.display());
+                return Ok(false);
+            }
         }
+    }
 
-        println!("\n   cached at:\n      {}", tags_file.as_ref().display());
+    // the record is only valid for the tags file written by this merge
+    if record_file.is_file() {
+        fs::remove_file(&record_file)?;
     }
 
-    let output = cmd.output()
-        .map_err(|err| format!("'ctags' execution failed: {}\nIs 'ctags' correctly installed?", err))?;
+    // the temporary file has to be on the same file system as the final
+    // tags file, otherwise the moving of the tags file wouldn't be atomic
+    let tags_dir = tags_file.parent().unwrap_or_else(|| Path::new("."));
+    let tmp_tags = NamedTempFile::new_in(tags_dir)?;
+    merge_keeping_foreign_tags(config, dep_tree, Some(tags_file), lib_tag_file, dependency_tag_files, tmp_tags.path())?;
+    move_tags(config, tmp_tags.path(), tags_file)?;
 
-    if ! output.status.success() {
-        let mut msg = String::from_utf8_lossy(&output.stderr).into_owned();
-        if msg.is_empty() {
-            msg = String::from_utf8_lossy(&output.stdout).into_owned();
-        }
+    if let Some(stamp) = tags_file_stamp(tags_file) {
+        fs::write(&record_file, format!("{}\n{}", inputs_hash, stamp))?;
+    }
+
+    Ok(true)
+}
+
+/// The file in the cache directory remembering the inputs of the last merge into `tags_file`.
+fn merge_record_file(config: &Config, tags_file: &Path) -> RtResult<PathBuf> {
+    let mut hasher = FnvHasher::default();
+    hasher.write(&path_bytes(&path::absolute(tags_file)?));
+    Ok(rusty_tags_cache_dir()?.join(format!("{}.{}.merged", hasher.finish(), config.tags_spec.file_extension())))
+}
+
+/// Hash of the paths and contents of the merged tags files and of the options
+/// which change the merged tags. The pseudo tags of vi tags aren't hashed, because
+/// they contain the time of the tags creation, which changes with every recreation
+/// of unchanged tags.
+fn merge_inputs_hash(config: &Config, lib_tag_file: &Path, dependency_tag_files: &[&Path]) -> RtResult<String> {
+    let mut hasher = FnvHasher::default();
+    hasher.write(ctags_command_hash(&config.tags_spec).as_bytes());
+    for option in &[config.append, config.relative_paths, config.canonicalize, config.dedup_tags] {
+        hasher.write_u8(*option as u8);
+    }
 
-        if msg.is_empty() {
-            msg = "ctags execution failed without any stderr or stdout output".to_string();
+    let skip_pseudo_tags = config.tags_spec.kind == TagsKind::Vi;
+    let mut line = Vec::new();
+    for (idx, file) in Some(lib_tag_file).into_iter().chain(dependency_tag_files.iter().cloned()).enumerate() {
+        // the library tags file is always a new temporary file
+        if idx > 0 {
+            hasher.write(&path_bytes(file));
         }
 
-        return Err(msg.into());
+        // the file separator, which can't be part of the hashed paths
+        hasher.write_u8(0);
+
+        let mut reader = BufReader::new(File::open(file)?);
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+
+            if ! (skip_pseudo_tags && line.starts_with(b"!_")) {
+                hasher.write(&line);
+            }
+        }
     }
 
-    Ok(())
+    Ok(hasher.finish().to_string())
 }
 
-pub fn copy_tags(config: &Config, from_tags: &Path, to_tags: &Path) -> RtResult<()> {
-    verbose!(config, "\nCopy tags ...\n   from:\n      {}\n   to:\n      {}",
-             from_tags.display(), to_tags.display());
+/// The size and modification time of `tags_file`, to detect changes of
+/// the tags file since the last merge, none if it doesn't exist.
+fn tags_file_stamp(tags_file: &Path) -> Option<String> {
+    let metadata = fs::metadata(tags_file).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some(format!("{} {}.{:09}", metadata.len(), modified.as_secs(), modified.subsec_nanos()))
+}
+
+/// merges the library tag file `lib_tag_file` and its dependency tag files
+/// `dependency_tag_files` into `into_tag_file`, with '--append' the tags of
+/// `existing_tag_file` which weren't created by rusty-tags are kept
+fn merge_keeping_foreign_tags(config: &Config,
+                              dep_tree: &DepTree,
+                              existing_tag_file: Option<&Path>,
+                              lib_tag_file: &Path,
+                              dependency_tag_files: &[&Path],
+                              into_tag_file: &Path)
+                              -> RtResult<()> {
+    let foreign_tags = match existing_tag_file {
+        Some(file) if config.append && file.is_file() => Some(read_foreign_tags(config, dep_tree, file)?),
+        _                                             => None
+    };
+
+    let mut dependency_tag_files = dependency_tag_files.to_vec();
+    if let (Some(ref foreign_tags), TagsKind::Vi) = (&foreign_tags, config.tags_spec.kind) {
+        dependency_tag_files.push(foreign_tags.path());
+    }
+
+    if ! dependency_tag_files.is_empty() {
+        merge_tags(config, lib_tag_file, &dependency_tag_files, into_tag_file)?;
+    } else {
+        copy_tags(config, lib_tag_file, into_tag_file)?;
+    }
+
+    // the emacs tags aren't sorted, so the foreign tags can be just appended
+    if let (Some(ref foreign_tags), TagsKind::Emacs) = (&foreign_tags, config.tags_spec.kind) {
+        let mut tag_file = OpenOptions::new().append(true).open(into_tag_file)?;
+        io::copy(&mut File::open(foreign_tags.path())?, &mut tag_file)?;
+    }
+
+    // `into_tag_file` is always created in the directory of the final tags file
+    if config.relative_paths {
+        let tags_dir = path::absolute(into_tag_file.parent().unwrap_or_else(|| Path::new(".")))?;
+        let mut relative_tags = NamedTempFile::new_in(&tags_dir)?;
+        {
+            let mut writer = BufWriter::with_capacity(64000, &mut relative_tags);
+            write_relative_tags(config, into_tag_file, &canonical_dir(config, &tags_dir), &mut writer)?;
+            writer.flush()?;
+        }
+
+        move_tags(config, relative_tags.path(), into_tag_file)?;
+    }
 
-    let _ = copy(from_tags, to_tags)?;
     Ok(())
 }
 
-pub fn move_tags(config: &Config, from_tags: &Path, to_tags: &Path) -> RtResult<()> {
-    verbose!(config, "\nMove tags ...\n   from:\n      {}\n   to:\n      {}",
-             from_tags.display(), to_tags.display());
+/// Writes the merged tags of `lib_tag_file` and `dependency_tag_files` to stdout. Stdout
+/// is locked during the whole writing, so that the tags of multiple roots are just
+/// concatenated. With '--relative-paths' the paths are relative to the current directory.
+fn write_merged_tags_to_stdout(config: &Config, lib_tag_file: &Path, dependency_tag_files: &[&Path]) -> RtResult<()> {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    if config.relative_paths {
+        let mut merged_tags = NamedTempFile::new()?;
+        write_merged_tags(config, lib_tag_file, dependency_tag_files, &mut merged_tags)?;
+        merged_tags.flush()?;
+        write_relative_tags(config, merged_tags.path(), &canonical_dir(config, &env::current_dir()?), &mut stdout)?;
+    } else {
+        write_merged_tags(config, lib_tag_file, dependency_tag_files, &mut stdout)?;
+    }
 
-    let _ = rename(from_tags, to_tags)?;
+    stdout.flush()?;
     Ok(())
 }
 
-/// merges the library tag file `lib_tag_file` and its dependency tag files
-/// `dependency_tag_files` into `into_tag_file`
-fn merge_tags(config: &Config,
-              lib_tag_file: &Path,
-              dependency_tag_files: &[&Path],
-              into_tag_file: &Path)
-              -> RtResult<()> {
-    if config.verbose {
-        println!("\nMerging ...\n   tags:");
-        println!("      {}", lib_tag_file.display());
-        for file in dependency_tag_files {
-            println!("      {}", file.display());
+/// Writes the tags of `tags_file` into `writer` with the paths of the source files
+/// relative to the absolute directory `base_dir`, which might start with '..'.
+fn write_relative_tags<W: Write>(config: &Config, tags_file: &Path, base_dir: &Path, writer: &mut W) -> RtResult<()> {
+    let relative = |file: &[u8]| -> Vec<u8> {
+        match relative_path(&path_from_bytes(file), base_dir) {
+            Some(path) => path_bytes(&path).into_owned(),
+            None       => file.to_vec()
         }
-        println!("\n   into:\n      {}", into_tag_file.display());
-    }
+    };
 
     match config.tags_spec.kind {
         TagsKind::Vi => {
-            if dependency_tag_files.is_empty() {
-                if lib_tag_file != into_tag_file {
-                    copy_tags(config, lib_tag_file, into_tag_file)?;
+            for line in tags_lines(BufReader::new(File::open(tags_file)?)) {
+                let line = line?;
+                let mut fields = line.splitn(3, |b| *b == b'\t');
+                match (fields.next(), fields.next(), fields.next()) {
+                    (Some(name), Some(file), Some(rest)) if is_tags_line(&line) => {
+                        writer.write_all(name)?;
+                        writer.write_all(b"\t")?;
+                        writer.write_all(&relative(file))?;
+                        writer.write_all(b"\t")?;
+                        writer.write_all(rest)?;
+                    }
+
+                    _ => writer.write_all(&line)?
                 }
 
-                return Ok(());
+                writer.write_all(b"\n")?;
             }
+        }
 
-            let mut file_contents: Vec<String> = Vec::with_capacity(dependency_tag_files.len() + 1);
-            let mut num_lines: usize = 0;
-            {
-                let mut file = File::open(lib_tag_file)?;
-                let mut contents = String::new();
-                file.read_to_string(&mut contents)?;
-                num_lines += contents.lines().count();
-                file_contents.push(contents);
-            }
+        TagsKind::Emacs => {
+            for section in read_etags_sections(tags_file)? {
+                let section = match section {
+                    EtagsSection::Tags { file, tags } => EtagsSection::Tags { file: relative(&file), tags },
+                    EtagsSection::Include { file }    => EtagsSection::Include { file: relative(&file) }
+                };
 
-            for file in dependency_tag_files {
-                let mut file = File::open(file)?;
-                let mut contents = String::new();
-                file.read_to_string(&mut contents)?;
-                num_lines += contents.lines().count();
-                file_contents.push(contents);
-            }
-
-            let mut merged_lines: Vec<&str> = Vec::with_capacity(num_lines);
-            for content in file_contents.iter() {
-                for line in content.lines() {
-                    if let Some(chr) = line.chars().nth(0) {
-                        if chr != '!' {
-                            merged_lines.push(line);
-                        }
-                    }
-                }
+                section.write(writer)?;
             }
+        }
 
-            verbose!(config, "\nNum merged lines: {}", merged_lines.len());
+        TagsKind::Gtags | TagsKind::Cscope => return Err(database_merge_error())
+    }
 
-            merged_lines.sort_unstable();
-            merged_lines.dedup();
+    Ok(())
+}
 
-            let mut tag_file = BufWriter::with_capacity(64000, OpenOptions::new()
-                .create(true)
-                .truncate(true)
-                .read(true)
-                .write(true)
-                .open(into_tag_file)?);
+/// Reads the tags of `tag_file` which weren't created by rusty-tags - which don't
+/// reference a file inside of the directory of any source of `dep_tree` or
+/// inside of the cache directory - and writes them into a temporary file.
+fn read_foreign_tags(config: &Config, dep_tree: &DepTree, tag_file: &Path) -> RtResult<NamedTempFile> {
+    let mut known_dirs: Vec<PathBuf> = dep_tree.all_sources().map(|s| s.dir.clone()).collect();
+    if config.canonicalize {
+        let canonical_dirs: Vec<_> = known_dirs.iter().filter_map(|d| fs::canonicalize(d).ok()).collect();
+        known_dirs.extend(canonical_dirs);
+    }
 
-            tag_file.write_fmt(format_args!("{}\n", "!_TAG_FILE_FORMAT	2	/extended format; --format=1 will not append ;\" to lines/"))?;
-            tag_file.write_fmt(format_args!("{}\n", "!_TAG_FILE_SORTED	1	/0=unsorted, 1=sorted, 2=foldcase/"))?;
+    known_dirs.push(rusty_tags_cache_dir()?.to_path_buf());
+    known_dirs.push(config.artifacts_dir()?);
 
-            let new_line = "\n".as_bytes();
-            for line in merged_lines {
-                tag_file.write_all(line.as_bytes())?;
-                tag_file.write_all(new_line)?;
-            }
-        },
+    // relative paths of '--relative-paths' are relative to the directory of the tags file
+    let tags_dir = canonical_dir(config, &path::absolute(tag_file.parent().unwrap_or_else(|| Path::new(".")))?);
+    let is_foreign = |file: &[u8]| {
+        let file = normalize_path(&tags_dir.join(path_from_bytes(file)));
+        ! known_dirs.iter().any(|dir| file.starts_with(dir))
+    };
 
-        TagsKind::Emacs => {
-            if lib_tag_file != into_tag_file {
-                copy_tags(config, lib_tag_file, into_tag_file)?;
-            }
+    let mut foreign_tags = NamedTempFile::new()?;
+    let mut num_tags: usize = 0;
+    match config.tags_spec.kind {
+        TagsKind::Vi => {
+            for line in tags_lines(BufReader::new(File::open(tag_file)?)) {
+                let line = line?;
+                if ! is_tags_line(&line) {
+                    continue;
+                }
 
-            let mut tag_file = BufWriter::with_capacity(64000, OpenOptions::new()
-                .create(true)
-                .append(true)
-                .read(true)
-                .write(true)
-                .open(into_tag_file)?);
+                if line.split(|b| *b == b'\t').nth(1).is_none_or(&is_foreign) {
+                    foreign_tags.write_all(&line)?;
+                    foreign_tags.write_all(b"\n")?;
+                    num_tags += 1;
+                }
+            }
+        }
 
-            for file in dependency_tag_files {
-                if *file != into_tag_file {
-                    tag_file.write_fmt(format_args!("{},include\n", file.display()))?;
+        TagsKind::Emacs => {
+            for section in read_etags_sections(tag_file)? {
+                if is_foreign(section.file()) {
+                    section.write(&mut foreign_tags)?;
+                    num_tags += 1;
                 }
             }
         }
+
+        TagsKind::Gtags | TagsKind::Cscope => return Err(database_merge_error())
     }
 
-    Ok(())
+    verbose!(config, "\nKeeping {} foreign tags of '{}'", num_tags, tag_file.display());
+    foreign_tags.flush()?;
+    Ok(foreign_tags)
 }
 
-type CrateName = String;
+/// Prints the sources - given by their versions - which tags creation failed
+/// while 'keep_going' and returns the summary of the tags creation.
+fn tags_summary(config: &Config, dep_tree: &DepTree, failures: Vec<(String, RtErr)>) -> TagsSummary {
+    if ! failures.is_empty() {
+        let msg = format!("Couldn't create the {} tags of {} sources:", config.tags_spec.file_extension(), failures.len());
+        eprintln!("{}", Style::Error.paint(config.colored, msg));
+        for (source_version, err) in &failures {
+            eprintln!("   {}: {}", Style::Name.paint(config.colored, source_version), err.to_string().trim_end());
+        }
+    }
 
-/// searches in the file `<src_dir>/lib.rs` for external crates
-/// that are reexpored and returns their names
-fn find_reexported_crates(src_dir: &Path) -> RtResult<Vec<CrateName>> {
-    let lib_file = src_dir.join("lib.rs");
-    if ! lib_file.is_file() {
-        return Ok(Vec::new());
+    let kind = config.tags_spec.kind;
+    let roots = dep_tree.roots().map(|root| {
+        let tags_file = if kind == TagsKind::Cscope || (config.workspace_tags && ! kind.is_database()) {
+            dep_tree.workspace_tags_file().map(Path::to_path_buf)
+        } else if config.tags_to_stdout && ! kind.is_database() {
+            None
+        } else if config.no_merge && ! kind.is_database() {
+            Some(root.cached_tags_file.clone())
+        } else {
+            Some(root.tags_file.clone())
+        };
+
+        // the tags are only counted for the summary, because this reads the whole tags file
+        let num_tags = match tags_file {
+            Some(ref file) if config.summary_json && ! kind.is_database() => count_tags(kind, file).ok(),
+            _ => None
+        };
+
+        RootSummary {
+            name: root.name.clone(),
+            version: root.version.to_string(),
+            src_dir: root.dir.clone(),
+            tags_file,
+            num_tags
+        }
+    })
+    .collect();
+
+    TagsSummary {
+        kind: kind.to_string(),
+        roots,
+        missing_sources: dep_tree.num_missing_sources(),
+        up_to_date: Vec::new(),
+        failures: failures.into_iter()
+            .map(|(source, err)| FailureSummary { source, error: err.to_string().trim_end().to_string() })
+            .collect(),
+        tag_files: Vec::new()
     }
+}
 
-    let contents = {
-        let mut file = File::open(&lib_file)?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
-        contents
+/// Validates the created tags file of 'root', which is an error with '--strict'
+/// and otherwise only a warning. This catches e.g. wrong ctags options, which
+/// let ctags silently create empty tags.
+fn check_root_tags(config: &Config, root: &str, tags_file: &Path) -> RtResult<()> {
+    let problem = match tags_file_problem(config.tags_spec.kind, tags_file)? {
+        Some(problem) => problem,
+        None          => return Ok(())
     };
 
-    let lines = contents.lines();
+    let msg = format!("The tags of {} at '{}' {}, are the ctags options correct?", root, tags_file.display(), problem);
+    if config.strict {
+        return Err(msg.into());
+    }
 
-    type ModuleName = String;
-    let mut pub_uses = FnvHashSet::<ModuleName>::default();
+    warn!(config, "{}", msg);
+    Ok(())
+}
 
-    #[derive(Eq, PartialEq, Hash)]
-    struct ExternCrate<'a>
-    {
-        name: &'a str,
-        as_name: &'a str
+/// Runs the '--post-hook' command by the shell after 'tags_file' of the root 'name' - or of
+/// the workspace without a name - was written. A failed hook is an error with '--strict'
+/// and otherwise only a warning.
+fn run_post_hook(config: &Config, name: Option<&str>, tags_file: &Path) -> RtResult<()> {
+    let hook = match config.post_hook {
+        Some(ref hook) => hook,
+        None           => return Ok(())
+    };
+
+    let mut cmd = shell_command(hook);
+    cmd.env("RUSTY_TAGS_FILE", tags_file)
+       .env("RUSTY_TAGS_KIND", config.tags_spec.kind.to_string());
+
+    if let Some(name) = name {
+        cmd.env("RUSTY_TAGS_CRATE", name);
     }
 
-    let mut extern_crates = FnvHashSet::<ExternCrate>::default();
+    verbose!(config, "\nRunning the post hook for '{}': {:?}", tags_file.display(), cmd);
 
-    for line in lines {
-        let items = line.trim_matches(';').split(' ').collect::<Vec<&str>>();
-        if items.len() < 3 {
-            continue;
-        }
+    let target = format!("the tags file '{}'", tags_file.display());
+    let result = match output_with_timeout(&mut cmd, config.timeout) {
+        Ok(Some(ref output)) if output.status.success() => Ok(()),
 
-        if items[0] == "pub" && items[1] == "use" {
+        Ok(Some(output)) => Err(RtErr::CommandFailed {
+            command: hook.clone(),
+            target,
+            status: output.status,
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned()
+        }),
+
+        Ok(None) => Err(RtErr::Timeout {
+            command: hook.clone(),
+            target,
+            secs: config.timeout.map(|t| t.as_secs()).unwrap_or(0)
+        }),
+
+        Err(err) => Err(format!("Couldn't run the post hook '{}': {}", hook, err).into())
+    };
+
+    match result {
+        Err(err) if ! config.strict => {
+            warn!(config, "The post hook failed: {}", err.to_string().trim_end());
+            Ok(())
+        }
+
+        result => result
+    }
+}
+
+#[cfg(unix)]
+fn shell_command(cmd: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(cmd);
+    shell
+}
+
+#[cfg(windows)]
+fn shell_command(cmd: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(cmd);
+    shell
+}
+
+/// What's wrong with the vi or emacs 'tags_file', none if it looks fine. Vi tags need
+/// the '!_TAG_FILE_FORMAT' header and a tag line, emacs tags a section.
+fn tags_file_problem(kind: TagsKind, tags_file: &Path) -> RtResult<Option<&'static str>> {
+    if fs::metadata(tags_file)?.len() == 0 {
+        return Ok(Some("are empty"));
+    }
+
+    match kind {
+        TagsKind::Vi => {
+            let mut has_header = false;
+            let mut has_tag = false;
+            for line in tags_lines(BufReader::new(File::open(tags_file)?)) {
+                let line = line?;
+                has_header |= line.starts_with(b"!_TAG_FILE_FORMAT");
+                has_tag |= is_tags_line(&line);
+                if has_header && has_tag {
+                    break;
+                }
+            }
+
+            if ! has_header {
+                Ok(Some("miss the '!_TAG_FILE_FORMAT' header"))
+            } else if ! has_tag {
+                Ok(Some("don't contain any tag"))
+            } else {
+                Ok(None)
+            }
+        }
+
+        TagsKind::Emacs if read_etags_sections(tags_file)?.is_empty() => Ok(Some("don't contain any section")),
+        _ => Ok(None)
+    }
+}
+
+/// The number of tags in the vi or emacs 'tags_file'.
+fn count_tags(kind: TagsKind, tags_file: &Path) -> RtResult<usize> {
+    let mut num_tags = 0;
+    for line in tags_lines(BufReader::new(File::open(tags_file)?)) {
+        let line = line?;
+        let is_tag = match kind {
+            TagsKind::Vi    => is_tags_line(&line),
+
+            // the tag definitions of emacs are separated by a delete character from the tag name
+            TagsKind::Emacs => line.contains(&b'\x7f'),
+            _               => false
+        };
+
+        if is_tag {
+            num_tags += 1;
+        }
+    }
+
+    Ok(num_tags)
+}
+
+/// Creates the GNU Global database of each root of 'dep_tree'. In contrast to
+/// the vi and emacs tags there's no merging with the dependencies, because
+/// 'global' can't combine multiple databases, so only the rust source files
+/// of the root itself are in its database.
+fn update_gtags(config: &Config, dep_tree: &DepTree) -> RtResult<TagsSummary> {
+    if config.dry_run {
+        output!(config.output_to_stderr, "\nWould create gtags for sources:");
+        for root in dep_tree.roots() {
+            output!(config.output_to_stderr, "   {}", root.source_version());
+            output!(config.output_to_stderr, "      source dir: {}", root.dir.display());
+            output!(config.output_to_stderr, "      database: {}", root.tags_file.display());
+        }
+
+        return Ok(tags_summary(config, dep_tree, Vec::new()));
+    }
+
+    let mut failures = Vec::new();
+    for root in dep_tree.roots() {
+        if let Err(err) = create_gtags(config, root) {
+            if ! config.keep_going {
+                return Err(err);
+            }
+
+            warn!(config, "Couldn't create the tags of {}, continuing because of '--keep-going'", root.source_version());
+            failures.push((root.source_version(), err));
+        }
+    }
+
+    Ok(tags_summary(config, dep_tree, failures))
+}
+
+/// Runs 'gtags' in the directory of the 'Cargo.toml' of 'source' for all of its not excluded
+/// rust source files. An existing database is updated incrementally, unless forced.
+fn create_gtags(config: &Config, source: &Source) -> RtResult<()> {
+    let db_dir = source.tags_file.parent()
+        .ok_or_else(|| format!("Invalid database path '{}'!", source.tags_file.display()))?;
+
+    if ! db_dir.is_dir() {
+        fs::create_dir_all(db_dir)?;
+    }
+
+    // 'gtags' only accepts source files below its working directory
+    let project_dir = find_dir_upwards_containing("Cargo.toml", &source.dir).unwrap_or(source.dir.clone());
+    let mut files_list = NamedTempFile::new()?;
+    for dir in source.src_dirs(config) {
+        for file in rust_files(config, &dir)? {
+            let file = file.strip_prefix(&project_dir).unwrap_or(&file);
+            files_list.write_all(&path_bytes(file))?;
+            files_list.write_all(b"\n")?;
+        }
+    }
+
+    files_list.flush()?;
+
+    let mut cmd = config.tags_spec.ctags_command();
+    cmd.current_dir(&project_dir)
+       .arg("-f")
+       .arg(files_list.path());
+
+    if source.tags_file.is_file() && ! config.force_recreate {
+        cmd.arg("-i");
+    }
+
+    cmd.arg(db_dir);
+
+    verbose!(config, "\nCreating gtags ...\n   with command: {:?}\n   in: {}", cmd, project_dir.display());
+    run_database_command(config, cmd, &source.dir)
+}
+
+/// Creates a single cscope database of the rust source files of all roots in 'dep_tree',
+/// which is written to the root of the workspace or into the output directory. The source
+/// files are given to cscope by the file 'cscope.files' beside of the database.
+fn update_cscope(config: &Config, dep_tree: &DepTree) -> RtResult<TagsSummary> {
+    let db_file = dep_tree.workspace_tags_file()
+        .ok_or("Couldn't determine the directory of the cscope database!")?;
+
+    let db_dir = db_file.parent()
+        .ok_or_else(|| format!("Invalid database path '{}'!", db_file.display()))?;
+
+    if config.dry_run {
+        output!(config.output_to_stderr, "\nWould create the cscope database for sources:");
+        for root in dep_tree.roots() {
+            output!(config.output_to_stderr, "   {}", root.source_version());
+            output!(config.output_to_stderr, "      source dir: {}", root.dir.display());
+        }
+
+        output!(config.output_to_stderr, "   database: {}", db_file.display());
+        return Ok(tags_summary(config, dep_tree, Vec::new()));
+    }
+
+    let mut files = Vec::new();
+    for root in dep_tree.roots() {
+        for dir in root.src_dirs(config) {
+            files.extend(rust_files(config, &dir)?);
+        }
+    }
+
+    files.sort();
+    files.dedup();
+
+    // cscope separates the file names by whitespace, so
+    // names containing whitespace have to be quoted
+    let files_list = db_dir.join(CSCOPE_FILES_LIST_NAME);
+    {
+        let mut writer = BufWriter::new(File::create(&files_list)?);
+        for file in &files {
+            let file = path_bytes(file);
+            if file.iter().any(u8::is_ascii_whitespace) {
+                writer.write_all(b"\"")?;
+                for byte in file.iter() {
+                    if *byte == b'\\' || *byte == b'"' {
+                        writer.write_all(b"\\")?;
+                    }
+
+                    writer.write_all(&[*byte])?;
+                }
+
+                writer.write_all(b"\"\n")?;
+            } else {
+                writer.write_all(&file)?;
+                writer.write_all(b"\n")?;
+            }
+        }
+
+        writer.flush()?;
+    }
+
+    let mut cmd = config.tags_spec.ctags_command();
+    cmd.current_dir(db_dir)
+       .arg("-b")
+       .arg("-k");
+
+    if config.force_recreate {
+        cmd.arg("-u");
+    }
+
+    cmd.arg("-i")
+       .arg(&files_list)
+       .arg("-f")
+       .arg(db_file);
+
+    verbose!(config, "\nCreating cscope database ...\n   with command: {:?}", cmd);
+    run_database_command(config, cmd, db_dir)?;
+
+    Ok(tags_summary(config, dep_tree, Vec::new()))
+}
+
+/// Runs 'cmd' creating the database of GNU Global or cscope for the sources at 'dir'.
+fn run_database_command(config: &Config, mut cmd: Command, dir: &Path) -> RtResult<()> {
+    let exe_name = config.tags_spec.exe_name();
+    let start = Instant::now();
+    let output = output_with_timeout(&mut cmd, config.timeout)
+        .map_err(|err| -> RtErr {
+            if err.kind() == io::ErrorKind::NotFound {
+                RtErr::MissingTagsExe {
+                    exes: vec![config.tags_spec.exe_name().to_string()],
+                    kind: config.tags_spec.kind
+                }
+            } else {
+                format!("'{}' execution failed: {}\nIs '{}' correctly installed?", exe_name, err, exe_name).into()
+            }
+        })?;
+
+    let output = output.ok_or_else(|| timeout_error(config, exe_name, &[dir]))?;
+
+    debug!("{:?} finished with {} in {:.2?}", cmd, output.status, start.elapsed());
+    verbose!(config, "\n   finished in {:.2?}", start.elapsed());
+
+    if ! output.status.success() {
+        return Err(command_failed_error(exe_name, &[dir], &output));
+    }
+
+    if config.verbosity > 1 && ! output.stderr.is_empty() {
+        output!(config.output_to_stderr, "\n   stderr of {}:\n{}", exe_name, String::from_utf8_lossy(&output.stderr));
+    }
+
+    Ok(())
+}
+
+/// checks that `tags_file` exists and is a tags file of `kind`, by looking
+/// at its first line, which is for emacs tags a form feed and for vi tags a
+/// header line starting with a '!' or a tag with tab separated fields
+pub fn check_tags_file(tags_file: &Path, kind: TagsKind) -> RtResult<()> {
+    if ! tags_file.is_file() {
+        return Err(format!("Couldn't find tags file '{}'!", tags_file.display()).into());
+    }
+
+    let mut first_line = String::new();
+    BufReader::new(File::open(tags_file)?).read_line(&mut first_line)?;
+
+    let first_line = first_line.trim_end_matches(['\r', '\n']);
+    let is_emacs = first_line.starts_with('\x0c');
+    let is_vi = first_line.starts_with('!') || first_line.contains('\t');
+    let matches_kind = match kind {
+        TagsKind::Vi    => is_vi && ! is_emacs,
+        TagsKind::Emacs => is_emacs,
+        TagsKind::Gtags | TagsKind::Cscope => false
+    };
+
+    if ! matches_kind && ! first_line.is_empty() {
+        let found_kind = if is_emacs { "emacs" } else if is_vi { "vi" } else { "unknown" };
+        return Err(format!("The tags file '{}' has to be of the kind {}, but seems to be of the kind {}!",
+                           tags_file.display(), kind, found_kind).into());
+    }
+
+    Ok(())
+}
+
+/// creates tags recursive for the directory hierarchies starting at `src_dirs`
+/// and writes them to `tags_file`
+pub fn create_tags<P1, P2>(config: &Config, src_dirs: &[P1], tags_file: P2) -> RtResult<()>
+    where P1: AsRef<Path>,
+          P2: AsRef<Path>
+{
+    let src_dirs: Vec<_> = src_dirs.iter().map(|d| canonical_dir(config, d.as_ref())).collect();
+    let mut cmd = config.tags_spec.ctags_command();
+    cmd.arg("-o")
+       .arg(tags_file.as_ref());
+
+    for dir in &src_dirs {
+        cmd.arg(dir);
+    }
+
+    if config.verbose {
+        output!(config.output_to_stderr, "\nCreating tags ...\n   with command: {:?}", cmd);
+
+        output!(config.output_to_stderr, "\n   for source:");
+        for dir in &src_dirs {
+            output!(config.output_to_stderr, "      {}", dir.display());
+        }
+
+        output!(config.output_to_stderr, "\n   cached at:\n      {}", tags_file.as_ref().display());
+    }
+
+    let start = Instant::now();
+    let output = output_with_timeout(&mut cmd, config.timeout)
+        .map_err(|err| -> RtErr {
+            if err.kind() == io::ErrorKind::NotFound {
+                RtErr::MissingTagsExe {
+                    exes: vec![config.tags_spec.exe_name().to_string()],
+                    kind: config.tags_spec.kind
+                }
+            } else {
+                format!("'ctags' execution failed: {}\nIs 'ctags' correctly installed?", err).into()
+            }
+        })?;
+
+    let output = output.ok_or_else(|| timeout_error(config, config.tags_spec.exe_name(), &src_dirs))?;
+
+    debug!("{:?} finished with {} in {:.2?}", cmd, output.status, start.elapsed());
+    verbose!(config, "\n   finished in {:.2?}", start.elapsed());
+
+    if ! output.status.success() {
+        return Err(command_failed_error(config.tags_spec.exe_name(), &src_dirs, &output));
+    }
+
+    if config.verbosity > 1 && ! output.stderr.is_empty() {
+        output!(config.output_to_stderr, "\n   stderr of ctags:\n{}", String::from_utf8_lossy(&output.stderr));
+    }
+
+    Ok(())
+}
+
+fn timeout_error<P: AsRef<Path>>(config: &Config, exe_name: &str, src_dirs: &[P]) -> RtErr {
+    RtErr::Timeout {
+        command: exe_name.to_string(),
+        target: sources_target(src_dirs),
+        secs: config.timeout.map(|t| t.as_secs()).unwrap_or(0)
+    }
+}
+
+/// The error of the failed execution of 'exe_name' for 'src_dirs' with 'output',
+/// if there's no stderr output, then the stdout output is reported.
+fn command_failed_error<P: AsRef<Path>>(exe_name: &str, src_dirs: &[P], output: &Output) -> RtErr {
+    let mut stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    if stderr.trim().is_empty() {
+        stderr = String::from_utf8_lossy(&output.stdout).into_owned();
+    }
+
+    RtErr::CommandFailed {
+        command: exe_name.to_string(),
+        target: sources_target(src_dirs),
+        status: output.status,
+        stderr
+    }
+}
+
+/// The description of 'src_dirs' for error messages.
+fn sources_target<P: AsRef<Path>>(src_dirs: &[P]) -> String {
+    match src_dirs {
+        [dir] => format!("the sources at '{}'", dir.as_ref().display()),
+        _     => {
+            let parent = src_dirs.first().and_then(|d| d.as_ref().parent()).unwrap_or(Path::new(""));
+            format!("{} source directories in '{}'", src_dirs.len(), parent.display())
+        }
+    }
+}
+
+pub fn copy_tags(config: &Config, from_tags: &Path, to_tags: &Path) -> RtResult<()> {
+    verbose!(config, "\nCopy tags ...\n   from:\n      {}\n   to:\n      {}",
+             from_tags.display(), to_tags.display());
+
+    let _ = copy(from_tags, to_tags)?;
+    Ok(())
+}
+
+pub fn move_tags(config: &Config, from_tags: &Path, to_tags: &Path) -> RtResult<()> {
+    verbose!(config, "\nMove tags ...\n   from:\n      {}\n   to:\n      {}",
+             from_tags.display(), to_tags.display());
+
+    rename(from_tags, to_tags)?;
+    Ok(())
+}
+
+/// merges the library tag file `lib_tag_file` and its dependency tag files
+/// `dependency_tag_files` into `into_tag_file`
+fn merge_tags(config: &Config,
+              lib_tag_file: &Path,
+              dependency_tag_files: &[&Path],
+              into_tag_file: &Path)
+              -> RtResult<()> {
+    if config.verbose {
+        output!(config.output_to_stderr, "\nMerging ...\n   tags:");
+        output!(config.output_to_stderr, "      {}", lib_tag_file.display());
+        for file in dependency_tag_files {
+            output!(config.output_to_stderr, "      {}", file.display());
+        }
+        output!(config.output_to_stderr, "\n   into:\n      {}", into_tag_file.display());
+    }
+
+    debug!("Merging '{}' and {:?} into '{}'", lib_tag_file.display(), dependency_tag_files, into_tag_file.display());
+    match config.tags_spec.kind {
+        TagsKind::Vi => {
+            if dependency_tag_files.is_empty() && lib_tag_file == into_tag_file {
+                return Ok(());
+            }
+
+            // even without dependencies the tags are merged, to ensure
+            // a sorted tags file without duplicates and a correct header
+
+            let mut tag_file = BufWriter::with_capacity(64000, OpenOptions::new()
+                .create(true)
+                .truncate(true)
+                .read(true)
+                .write(true)
+                .open(into_tag_file)?);
+
+            write_merged_tags(config, lib_tag_file, dependency_tag_files, &mut tag_file)?;
+        },
+
+        // the sections are read before writing, because `into_tag_file`
+        // might be `lib_tag_file`
+        TagsKind::Emacs => {
+            let dependency_tag_files: Vec<_> = dependency_tag_files.iter()
+                .filter(|f| **f != into_tag_file)
+                .cloned()
+                .collect();
+
+            let sections = merged_etags_sections(lib_tag_file, &dependency_tag_files)?;
+            let mut tag_file = BufWriter::with_capacity(64000, OpenOptions::new()
+                .create(true)
+                .truncate(true)
+                .write(true)
+                .open(into_tag_file)?);
+
+            for section in &sections {
+                section.write(&mut tag_file)?;
+            }
+
+            tag_file.flush()?;
+        }
+
+        TagsKind::Gtags | TagsKind::Cscope => return Err(database_merge_error())
+    }
+
+    Ok(())
+}
+
+type TagsLines = Box<dyn Iterator<Item=io::Result<Vec<u8>>>>;
+
+/// The lines of `reader` without their line endings. The lines are bytes, because
+/// the paths in tags files don't have to be valid UTF-8.
+fn tags_lines<R: BufRead>(reader: R) -> impl Iterator<Item=io::Result<Vec<u8>>> {
+    reader.split(b'\n').map(|line| line.map(|mut line| {
+        if line.ends_with(b"\r") {
+            line.pop();
+        }
+
+        line
+    }))
+}
+
+/// Returns the lines of the vi tags file `tags_file` in the order of `sort`. Already
+/// sorted files are read line by line, otherwise the whole file is read and sorted.
+fn sorted_tags_lines(sort: TagsSort, tags_file: &Path) -> RtResult<TagsLines> {
+    let is_sorted = {
+        let mut is_sorted = true;
+        let mut last_line = Vec::new();
+        for line in tags_lines(BufReader::new(File::open(tags_file)?)) {
+            let line = line?;
+            if ! is_tags_line(&line) {
+                continue;
+            }
+
+            if sort.compare(&line, &last_line) == Ordering::Less {
+                is_sorted = false;
+                break;
+            }
+
+            last_line = line;
+        }
+
+        is_sorted
+    };
+
+    if is_sorted {
+        return Ok(Box::new(tags_lines(BufReader::new(File::open(tags_file)?))));
+    }
+
+    let mut lines = Vec::new();
+    for line in tags_lines(BufReader::new(File::open(tags_file)?)) {
+        let line = line?;
+        if is_tags_line(&line) {
+            lines.push(line);
+        }
+    }
+
+    lines.sort_unstable_by(|a, b| sort.compare(a, b));
+    Ok(Box::new(lines.into_iter().map(Ok)))
+}
+
+/// A current line of the `idx`th merged vi tags file, ordered by `sort`
+/// and then by `idx`, so that equal lines are written in file order.
+struct MergedLine {
+    line: Vec<u8>,
+    idx: usize,
+    sort: TagsSort
+}
+
+impl Ord for MergedLine {
+    fn cmp(&self, other: &MergedLine) -> Ordering {
+        self.sort.compare(&self.line, &other.line).then(self.idx.cmp(&other.idx))
+    }
+}
+
+impl PartialOrd for MergedLine {
+    fn partial_cmp(&self, other: &MergedLine) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for MergedLine {
+    fn eq(&self, other: &MergedLine) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for MergedLine {}
+
+/// Writes the pseudo tags of a merged vi tags file. Besides of the format and the sorting they
+/// record what created the tags: the version of rusty-tags, the ctags executable with its
+/// version and options, and the time of the merge. The pseudo tags are sorted by their names,
+/// like the tags following them.
+fn write_vi_header<W: Write>(config: &Config, writer: &mut W) -> RtResult<()> {
+    let tags_spec = &config.tags_spec;
+    let ctags = match tags_spec.exe_version() {
+        Some(version) => format!("{} ({})", tags_spec.exe_name(), version),
+        None          => tags_spec.exe_name().to_string()
+    };
+
+    let ctags_options: Vec<_> = tags_spec.ctags_command().get_args().map(|a| a.to_string_lossy().into_owned()).collect();
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+
+    writer.write_fmt(format_args!("{}\n", "!_TAG_FILE_FORMAT	2	/extended format; --format=1 will not append ;\" to lines/"))?;
+    writer.write_fmt(format_args!("!_TAG_FILE_SORTED	{}	/0=unsorted, 1=sorted, 2=foldcase/\n", config.tags_sort.pseudo_tag_value()))?;
+    writer.write_fmt(format_args!("!_TAG_PROGRAM_NAME	rusty-tags	//\n"))?;
+    writer.write_fmt(format_args!("!_TAG_PROGRAM_URL	{}	/official site/\n", env!("CARGO_PKG_REPOSITORY")))?;
+    writer.write_fmt(format_args!("!_TAG_PROGRAM_VERSION	{}	//\n", env!("CARGO_PKG_VERSION")))?;
+    writer.write_fmt(format_args!("!_TAG_RUSTY_TAGS_CTAGS	{}	/the ctags executable/\n", pseudo_tag_value(&ctags)))?;
+    writer.write_fmt(format_args!("!_TAG_RUSTY_TAGS_CTAGS_OPTIONS	{}	/the options of ctags/\n", pseudo_tag_value(&ctags_options.join(" "))))?;
+    writer.write_fmt(format_args!("!_TAG_RUSTY_TAGS_TIME	{}	/seconds since the unix epoch/\n", time))?;
+    Ok(())
+}
+
+/// 'value' without the tabs and line breaks, which would break the pseudo tag line.
+fn pseudo_tag_value(value: &str) -> String {
+    value.replace(['\t', '\n', '\r'], " ")
+}
+
+/// Returns the next line of `lines`, which isn't empty or a header line.
+fn next_tags_line(lines: &mut TagsLines) -> RtResult<Option<Vec<u8>>> {
+    for line in lines {
+        let line = line?;
+        if is_tags_line(&line) {
+            return Ok(Some(line));
+        }
+    }
+
+    Ok(None)
+}
+
+/// If `line` of a vi tags file contains a tag, so isn't empty and isn't a header line starting with a '!'.
+fn is_tags_line(line: &[u8]) -> bool {
+    ! line.is_empty() && ! line.starts_with(b"!")
+}
+
+/// The name, file and pattern of the tag `line` of a vi tags file, which is
+/// the line without the extension fields after the last ';"'.
+fn tag_location(line: &[u8]) -> &[u8] {
+    match line.windows(2).rposition(|w| w == b";\"") {
+        Some(pos) => &line[..pos],
+        None      => line
+    }
+}
+
+/// merges the library tag file `lib_tag_file` and its dependency tag files
+/// `dependency_tag_files` and writes the result into `writer`
+fn write_merged_tags<W: Write>(config: &Config,
+                               lib_tag_file: &Path,
+                               dependency_tag_files: &[&Path],
+                               writer: &mut W)
+                               -> RtResult<()> {
+    match config.tags_spec.kind {
+        // Unsorted tags are just concatenated, so duplicates can't be detected.
+        TagsKind::Vi if config.tags_sort == TagsSort::No => {
+            write_vi_header(config, writer)?;
+
+            let mut num_lines: usize = 0;
+            for file in Some(lib_tag_file).into_iter().chain(dependency_tag_files.iter().cloned()) {
+                for line in tags_lines(BufReader::new(File::open(file)?)) {
+                    let line = line?;
+                    if is_tags_line(&line) {
+                        writer.write_all(&line)?;
+                        writer.write_all(b"\n")?;
+                        num_lines += 1;
+                    }
+                }
+            }
+
+            verbose!(config, "\nNum merged lines: {}", num_lines);
+        },
+
+        // The tags files are merged line by line, by always writing the smallest
+        // of the current lines of all files, so that only one line per file has
+        // to be kept in memory. This needs sorted tags files, which is the
+        // default of ctags and which is ensured for the files written here.
+        TagsKind::Vi => {
+            let mut tags_files = Vec::with_capacity(dependency_tag_files.len() + 1);
+            tags_files.push(sorted_tags_lines(config.tags_sort, lib_tag_file)?);
+            for file in dependency_tag_files {
+                tags_files.push(sorted_tags_lines(config.tags_sort, file)?);
+            }
+
+            write_vi_header(config, writer)?;
+
+            let mut current_lines = BinaryHeap::with_capacity(tags_files.len());
+            for (idx, lines) in tags_files.iter_mut().enumerate() {
+                if let Some(line) = next_tags_line(lines)? {
+                    current_lines.push(Reverse(MergedLine { line, idx, sort: config.tags_sort }));
+                }
+            }
+
+            // Tags with the same name, file and pattern only differ in their extension
+            // fields, so only the first of them is written. In the sorted merge they're
+            // in a run of lines with equal locations, which with 'foldcase' also contains
+            // the locations only differing by case, so all locations of the run are kept.
+            let mut num_lines: usize = 0;
+            let mut num_duplicates: usize = 0;
+            let mut run_locations: Vec<Vec<u8>> = Vec::new();
+            let new_line = "\n".as_bytes();
+            while let Some(Reverse(MergedLine { line, idx, .. })) = current_lines.pop() {
+                if let Some(next_line) = next_tags_line(&mut tags_files[idx])? {
+                    current_lines.push(Reverse(MergedLine { line: next_line, idx, sort: config.tags_sort }));
+                }
+
+                if config.dedup_tags {
+                    let location = tag_location(&line);
+                    let is_same_run = run_locations.first()
+                        .is_some_and(|l| config.tags_sort.is_equivalent(l, location));
+
+                    if ! is_same_run {
+                        run_locations.clear();
+                    }
+
+                    if run_locations.iter().any(|l| l.as_slice() == location) {
+                        num_duplicates += 1;
+                        continue;
+                    }
+
+                    run_locations.push(location.to_vec());
+                }
+
+                writer.write_all(&line)?;
+                writer.write_all(new_line)?;
+                num_lines += 1;
+            }
+
+            verbose!(config, "\nNum merged lines: {}", num_lines);
+            if num_duplicates > 0 {
+                verbose!(config, "Num skipped duplicate lines: {}", num_duplicates);
+            }
+        },
+
+        // The emacs tags of the dependencies are only referenced by include
+        // sections, so there's nothing to merge and only the sections of the
+        // library itself are written.
+        TagsKind::Emacs => {
+            for section in &merged_etags_sections(lib_tag_file, dependency_tag_files)? {
+                section.write(writer)?;
+            }
+        }
+
+        TagsKind::Gtags | TagsKind::Cscope => return Err(database_merge_error())
+    }
+
+    Ok(())
+}
+
+/// A section of an emacs tags file, which starts with a form feed followed by a
+/// header line with the file of the tags and the size of the tags in bytes, or
+/// with 'include' for a section referencing another tags file.
+#[derive(PartialEq, Eq)]
+enum EtagsSection {
+    Tags { file: Vec<u8>, tags: Vec<u8> },
+    Include { file: Vec<u8> }
+}
+
+impl EtagsSection {
+    fn file(&self) -> &[u8] {
+        match *self {
+            EtagsSection::Tags { ref file, .. } | EtagsSection::Include { ref file } => file
+        }
+    }
+
+    /// Writes the section, the size in the header is always computed
+    /// from the written tags, so it can't get out of sync.
+    fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(b"\x0c\n")?;
+        writer.write_all(self.file())?;
+        match *self {
+            EtagsSection::Tags { ref tags, .. } => {
+                let missing_new_line = ! tags.is_empty() && ! tags.ends_with(b"\n");
+                let size = tags.len() + if missing_new_line { 1 } else { 0 };
+                writer.write_fmt(format_args!(",{}\n", size))?;
+                writer.write_all(tags)?;
+                if missing_new_line {
+                    writer.write_all(b"\n")?;
+                }
+            }
+
+            EtagsSection::Include { .. } => writer.write_all(b",include\n")?
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads the sections of the emacs tags file `tags_file`.
+fn read_etags_sections(tags_file: &Path) -> RtResult<Vec<EtagsSection>> {
+    let invalid = |reason: &str| -> RtErr {
+        format!("Invalid emacs tags file '{}': {}!", tags_file.display(), reason).into()
+    };
+
+    let mut contents = Vec::new();
+    File::open(tags_file)?.read_to_end(&mut contents)?;
+
+    let mut chunks = contents.split(|b| *b == b'\x0c');
+    if chunks.next().is_some_and(|c| c.iter().any(|b| ! b.is_ascii_whitespace())) {
+        return Err(invalid("content before the first section"));
+    }
+
+    let mut sections = Vec::new();
+    for chunk in chunks {
+        let chunk = chunk.strip_prefix(b"\r").unwrap_or(chunk);
+        let chunk = chunk.strip_prefix(b"\n").unwrap_or(chunk);
+        let (header, tags) = match chunk.iter().position(|b| *b == b'\n') {
+            Some(pos) => (&chunk[..pos], &chunk[pos + 1..]),
+            None      => (chunk, &[][..])
+        };
+
+        let header = header.strip_suffix(b"\r").unwrap_or(header);
+        let comma = header.iter().rposition(|b| *b == b',')
+            .ok_or_else(|| invalid("section header without a comma"))?;
+
+        let (file, size) = (header[..comma].to_vec(), &header[comma + 1..]);
+        if size == b"include" {
+            sections.push(EtagsSection::Include { file });
+        } else if ! size.is_empty() && size.iter().all(u8::is_ascii_digit) {
+            sections.push(EtagsSection::Tags { file, tags: tags.to_vec() });
+        } else {
+            return Err(invalid("section header without a size"));
+        }
+    }
+
+    Ok(sections)
+}
+
+/// The sections of the emacs tags file `lib_tag_file` followed by include
+/// sections for `dependency_tag_files`, without any duplicated sections.
+fn merged_etags_sections(lib_tag_file: &Path, dependency_tag_files: &[&Path]) -> RtResult<Vec<EtagsSection>> {
+    let mut sections: Vec<EtagsSection> = Vec::new();
+    let mut add_section = |section: EtagsSection| {
+        if ! sections.contains(&section) {
+            sections.push(section);
+        }
+    };
+
+    for section in read_etags_sections(lib_tag_file)? {
+        add_section(section);
+    }
+
+    for file in dependency_tag_files {
+        add_section(EtagsSection::Include { file: path_bytes(file).into_owned() });
+    }
+
+    Ok(sections)
+}
+
+/// The bytes of `path` as written into the tags files and the file lists. On unix
+/// these are the unchanged bytes, so that paths which aren't valid UTF-8 are kept.
+#[cfg(unix)]
+pub fn path_bytes(path: &Path) -> Cow<'_, [u8]> {
+    Cow::Borrowed(path.as_os_str().as_bytes())
+}
+
+#[cfg(not(unix))]
+pub fn path_bytes(path: &Path) -> Cow<'_, [u8]> {
+    match path.to_string_lossy() {
+        Cow::Borrowed(path) => Cow::Borrowed(path.as_bytes()),
+        Cow::Owned(path)    => Cow::Owned(path.into_bytes())
+    }
+}
+
+/// The path of the bytes `bytes` read from a tags file, the reverse of `path_bytes`.
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> Cow<'_, Path> {
+    Cow::Borrowed(Path::new(OsStr::from_bytes(bytes)))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> Cow<'_, Path> {
+    Cow::Owned(PathBuf::from(String::from_utf8_lossy(bytes).into_owned()))
+}
+
+/// With '--canonicalize' the canonical path of `dir`, which falls back to `dir`
+/// with a warning if it couldn't be resolved, otherwise just `dir`.
+fn canonical_dir(config: &Config, dir: &Path) -> PathBuf {
+    if ! config.canonicalize {
+        return dir.to_path_buf();
+    }
+
+    fs::canonicalize(dir).unwrap_or_else(|err| {
+        warn!(config, "Couldn't canonicalize '{}', using it as it is: {}", dir.display(), err);
+        dir.to_path_buf()
+    })
+}
+
+/// The path of the absolute `path` relative to the absolute directory `base_dir`.
+/// Returns 'None' if `path` is relative or if both paths have different roots,
+/// e.g. different drives.
+fn relative_path(path: &Path, base_dir: &Path) -> Option<PathBuf> {
+    if path.is_relative() {
+        return None;
+    }
+
+    let path = normalize_path(path);
+    let base_dir = normalize_path(base_dir);
+    let mut path_comps = path.components().peekable();
+    let mut base_comps = base_dir.components().peekable();
+    if path_comps.peek() != base_comps.peek() {
+        return None;
+    }
+
+    while path_comps.peek().is_some() && path_comps.peek() == base_comps.peek() {
+        path_comps.next();
+        base_comps.next();
+    }
+
+    let mut relative = PathBuf::new();
+    for _ in base_comps {
+        relative.push("..");
+    }
+
+    relative.extend(path_comps);
+    Some(relative)
+}
+
+/// `path` with its '.' and '..' components lexically resolved.
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for comp in path.components() {
+        match comp {
+            Component::CurDir    => {}
+            Component::ParentDir => {
+                if ! normalized.pop() {
+                    normalized.push(comp);
+                }
+            }
+
+            _ => normalized.push(comp)
+        }
+    }
+
+    normalized
+}
+
+fn database_merge_error() -> RtErr {
+    "The databases of GNU Global and cscope can't be merged!".into()
+}
+
+type CrateName = String;
+
+/// searches in the file `<src_dir>/lib.rs` for external crates
+/// that are reexpored and returns their names
+fn find_reexported_crates(src_dir: &Path) -> RtResult<Vec<CrateName>> {
+    let lib_file = src_dir.join("lib.rs");
+    if ! lib_file.is_file() {
+        return Ok(Vec::new());
+    }
+
+    let contents = {
+        let mut file = File::open(&lib_file)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        contents
+    };
+
+    let lines = contents.lines();
+
+    type ModuleName = String;
+    let mut pub_uses = FnvHashSet::<ModuleName>::default();
+
+    #[derive(Eq, PartialEq, Hash)]
+    struct ExternCrate<'a>
+    {
+        name: &'a str,
+        as_name: &'a str
+    }
+
+    let mut extern_crates = FnvHashSet::<ExternCrate>::default();
+
+    for line in lines {
+        let items = line.trim_matches(';').split(' ').collect::<Vec<&str>>();
+        if items.len() < 3 {
+            continue;
+        }
+
+        if items[0] == "pub" && items[1] == "use" {
             let mods = items[2].split("::").collect::<Vec<&str>>();
-            if mods.len() >= 1 {
+            if !mods.is_empty() {
                 pub_uses.insert(mods[0].to_string());
             }
         }
@@ -386,3 +1821,350 @@ fn find_reexported_crates(src_dir: &Path) -> RtResult<Vec<CrateName>> {
 
     Ok(reexp_crates)
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use config::Config;
+    use test_support::{test_config, test_dir, test_source, write_file};
+    use types::UpdateStatus;
+    use super::*;
+
+    const VI_HEADER: &str = "!_TAG_FILE_FORMAT\t2\t/extended format/\n!_TAG_FILE_SORTED\t1\t/0=unsorted, 1=sorted, 2=foldcase/\n";
+
+    /// Writes the vi tags file 'name' in 'dir' with the header of ctags and 'lines'.
+    fn vi_tags_file(dir: &Path, name: &str, lines: &[&str]) -> PathBuf {
+        let file = dir.join(name);
+        write_file(&file, &format!("{}{}", VI_HEADER, lines.iter().map(|l| format!("{}\n", l)).collect::<String>()));
+        file
+    }
+
+    /// Merges 'lib_tags' and 'dep_tags' by 'config' and returns the merged lines.
+    fn merged_lines(config: &Config, lib_tags: &Path, dep_tags: &[&Path]) -> Vec<String> {
+        let mut merged = Vec::new();
+        write_merged_tags(config, lib_tags, dep_tags, &mut merged).unwrap();
+        String::from_utf8(merged).unwrap().lines().map(str::to_string).collect()
+    }
+
+    /// The lines of 'lines' which aren't pseudo tags.
+    fn tag_lines(lines: &[String]) -> Vec<&str> {
+        lines.iter().map(String::as_str).filter(|l| ! l.starts_with("!_")).collect()
+    }
+
+    #[test]
+    fn merged_vi_tags_are_sorted_without_duplicates() {
+        let dir = test_dir("merged_vi_tags_are_sorted_without_duplicates");
+        let lib_tags = vi_tags_file(&dir, "lib.vi", &[
+            "Alpha\tsrc/lib.rs\t/^struct Alpha/;\"\ts",
+            "beta\tsrc/lib.rs\t/^fn beta/;\"\tf",
+            "delta\tsrc/lib.rs\t/^fn delta/;\"\tf"
+        ]);
+
+        let dep_tags = vi_tags_file(&dir, "dep.vi", &[
+            "Alpha\tsrc/lib.rs\t/^struct Alpha/;\"\ts",
+            "beta\tdep/lib.rs\t/^fn beta/;\"\tf",
+            "gamma\tdep/lib.rs\t/^fn gamma/;\"\tf"
+        ]);
+
+        let config = test_config(&dir, &["vi"]);
+        let lines = merged_lines(&config, &lib_tags, &[&dep_tags]);
+        assert_eq!(tag_lines(&lines), vec![
+            "Alpha\tsrc/lib.rs\t/^struct Alpha/;\"\ts",
+            "beta\tdep/lib.rs\t/^fn beta/;\"\tf",
+            "beta\tsrc/lib.rs\t/^fn beta/;\"\tf",
+            "delta\tsrc/lib.rs\t/^fn delta/;\"\tf",
+            "gamma\tdep/lib.rs\t/^fn gamma/;\"\tf"
+        ]);
+
+        assert!(lines.contains(&"!_TAG_FILE_SORTED\t1\t/0=unsorted, 1=sorted, 2=foldcase/".to_string()), "{:?}", lines);
+
+        // the header comes first and is sorted like the tags
+        let num_header_lines = lines.iter().take_while(|l| l.starts_with("!_")).count();
+        assert_eq!(num_header_lines, lines.len() - tag_lines(&lines).len());
+        assert!(lines.windows(2).all(|w| w[0] < w[1]), "{:?}", lines);
+    }
+
+    #[test]
+    fn dedup_skips_exact_duplicates() {
+        let dir = test_dir("dedup_skips_exact_duplicates");
+        let line = "serialize\tstd/lib.rs\t/^fn serialize/;\"\tf";
+        let std_tags = vi_tags_file(&dir, "std.vi", &[line]);
+        let dep_tags = vi_tags_file(&dir, "dep.vi", &[line, line]);
+
+        let lines = merged_lines(&test_config(&dir, &["vi"]), &std_tags, &[&dep_tags]);
+        assert_eq!(tag_lines(&lines), vec![line]);
+
+        let lines = merged_lines(&test_config(&dir, &["vi", "--no-dedup"]), &std_tags, &[&dep_tags]);
+        assert_eq!(tag_lines(&lines), vec![line, line, line]);
+    }
+
+    #[test]
+    fn dedup_keeps_the_same_name_at_different_locations() {
+        let dir = test_dir("dedup_keeps_the_same_name_at_different_locations");
+        let lib_tags = vi_tags_file(&dir, "lib.vi", &[
+            "new\tsrc/a.rs\t/^fn new(a)/;\"\tf",
+            "new\tsrc/b.rs\t/^fn new(a)/;\"\tf"
+        ]);
+
+        // the same location with other extension fields, and locations
+        // whose patterns only differ after a common prefix
+        let dep_tags = vi_tags_file(&dir, "dep.vi", &[
+            "new\tsrc/a.rs\t/^fn new(a)/;\"\tf\tline:3",
+            "new\tsrc/a.rs\t/^fn new(a: T)/;\"\tf",
+            "new\tsrc/a.rs\t/^fn new(a)x/;\"\tf"
+        ]);
+
+        let lines = merged_lines(&test_config(&dir, &["vi"]), &lib_tags, &[&dep_tags]);
+        assert_eq!(tag_lines(&lines), vec![
+            "new\tsrc/a.rs\t/^fn new(a)/;\"\tf",
+            "new\tsrc/a.rs\t/^fn new(a)x/;\"\tf",
+            "new\tsrc/a.rs\t/^fn new(a: T)/;\"\tf",
+            "new\tsrc/b.rs\t/^fn new(a)/;\"\tf"
+        ]);
+    }
+
+    #[test]
+    fn dedup_with_foldcase_skips_duplicates_between_other_cases() {
+        let dir = test_dir("dedup_with_foldcase_skips_duplicates_between_other_cases");
+
+        // ignoring the case 'Foo' sorts between the 'foo' lines
+        let lib_tags = vi_tags_file(&dir, "lib.vi", &["foo\tsrc/a.rs\t/^fn foo/;\"\ta"]);
+        let dep_tags = vi_tags_file(&dir, "dep.vi", &["Foo\tsrc/a.rs\t/^fn foo/;\"\tb", "foo\tsrc/a.rs\t/^fn foo/;\"\tc"]);
+
+        let lines = merged_lines(&test_config(&dir, &["vi", "--sort", "foldcase"]), &lib_tags, &[&dep_tags]);
+        assert_eq!(tag_lines(&lines), vec!["foo\tsrc/a.rs\t/^fn foo/;\"\ta", "Foo\tsrc/a.rs\t/^fn foo/;\"\tb"]);
+    }
+
+    #[test]
+    fn header_records_the_sorting() {
+        let dir = test_dir("header_records_the_sorting");
+        for &(sort, value) in &[("yes", 1), ("foldcase", 2), ("no", 0)] {
+            let mut header = Vec::new();
+            write_vi_header(&test_config(&dir, &["vi", "--sort", sort]), &mut header).unwrap();
+            let header = String::from_utf8(header).unwrap();
+            assert!(header.lines().any(|l| l == format!("!_TAG_FILE_SORTED\t{}\t/0=unsorted, 1=sorted, 2=foldcase/", value)),
+                    "--sort {}: {}", sort, header);
+        }
+    }
+
+    #[test]
+    fn merged_etags_have_well_formed_sections() {
+        let dir = test_dir("merged_etags_have_well_formed_sections");
+
+        // sizes which don't match the tags, a section without a final new
+        // line and an include section, which are all kept by the merge
+        let lib_tags = dir.join("lib.emacs");
+        write_file(&lib_tags, "\x0c\nsrc/lib.rs,3\nfn alpha\x7falpha\x011,0\n\
+                               \x0c\nsrc/a.rs,100\nfn beta\x7fbeta\x012,5\
+                               \x0c\nstd.emacs,include\n");
+
+        let dep_tags = dir.join("dep.emacs");
+        let mut merged = Vec::new();
+        write_merged_tags(&test_config(&dir, &["emacs"]), &lib_tags, &[&dep_tags, &dep_tags], &mut merged).unwrap();
+
+        let mut sections = Vec::new();
+        let mut chunks = merged.split(|b| *b == b'\x0c');
+        assert_eq!(chunks.next(), Some(&b""[..]));
+        for chunk in chunks {
+            let chunk = chunk.strip_prefix(b"\n").expect("new line after the form feed");
+            let header_end = chunk.iter().position(|b| *b == b'\n').expect("header line");
+            let header = String::from_utf8(chunk[..header_end].to_vec()).unwrap();
+            let tags = &chunk[header_end + 1..];
+            let (file, size) = header.rsplit_once(',').expect("comma in the header");
+            if size == "include" {
+                assert!(tags.is_empty(), "tags in the include section {}", file);
+            } else {
+                assert_eq!(size.parse::<usize>().unwrap(), tags.len(), "size of the section {}", file);
+                assert!(tags.ends_with(b"\n"), "section {} ends with a new line", file);
+            }
+
+            sections.push(header);
+        }
+
+        let dep_header = format!("{},include", dep_tags.display());
+        assert_eq!(sections, vec!["src/lib.rs,19", "src/a.rs,17", "std.emacs,include", &dep_header]);
+    }
+
+    /// A writer which fails after 'remaining' bytes.
+    struct FailingWriter { remaining: usize }
+
+    impl Write for FailingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.remaining == 0 {
+                return Err(io::Error::other("disk full"));
+            }
+
+            let len = buf.len().min(self.remaining);
+            self.remaining -= len;
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn write_errors_of_the_merge_are_returned() {
+        let dir = test_dir("write_errors_of_the_merge_are_returned");
+        let lib_tags = vi_tags_file(&dir, "lib.vi", &["alpha\tsrc/lib.rs\t/^fn alpha/;\"\tf"]);
+        let dep_tags = vi_tags_file(&dir, "dep.vi", &["beta\tdep/lib.rs\t/^fn beta/;\"\tf"]);
+
+        let config = test_config(&dir, &["vi"]);
+        let err = write_merged_tags(&config, &lib_tags, &[&dep_tags], &mut FailingWriter { remaining: 100 }).unwrap_err();
+        assert!(err.to_string().contains("disk full"), "{}", err);
+    }
+
+    #[test]
+    fn failed_merge_keeps_the_tags_file() {
+        let dir = test_dir("failed_merge_keeps_the_tags_file");
+        let project_dir = dir.join("project");
+        let tags_file = project_dir.join("rusty-tags.emacs");
+        write_file(&tags_file, "\x0c\nsrc/lib.rs,19\nfn alpha\x7falpha\x011,0\n");
+
+        // only fails while merging, after the creation of the temporary tags file
+        let lib_tags = dir.join("lib.emacs");
+        write_file(&lib_tags, "no emacs tags");
+        let dep_tags = dir.join("dep.emacs");
+        write_file(&dep_tags, "");
+
+        let config = test_config(&project_dir, &["emacs"]);
+        assert!(update_root_tags(&config, &DepTree::new(), &tags_file, &lib_tags, &[&dep_tags]).is_err());
+
+        assert_eq!(fs::read_to_string(&tags_file).unwrap(), "\x0c\nsrc/lib.rs,19\nfn alpha\x7falpha\x011,0\n");
+        let files: Vec<_> = fs::read_dir(&project_dir).unwrap().map(|e| e.unwrap().path()).collect();
+        assert_eq!(files, vec![tags_file]);
+    }
+
+    #[test]
+    fn merge_inputs_hash_ignores_the_pseudo_tags() {
+        let dir = test_dir("merge_inputs_hash_ignores_the_pseudo_tags");
+        let config = test_config(&dir, &["vi"]);
+        let lib_tags = vi_tags_file(&dir, "lib.vi", &["alpha\tsrc/lib.rs\t/^fn alpha/;\"\tf"]);
+        let dep_tags_hash = |lines: &[&str]| {
+            let dep_tags = vi_tags_file(&dir, "dep.vi", lines);
+            merge_inputs_hash(&config, &lib_tags, &[&dep_tags]).unwrap()
+        };
+
+        let hash = dep_tags_hash(&["!_TAG_RUSTY_TAGS_TIME\t1\t/seconds since the unix epoch/", "beta\tdep/lib.rs\t/^fn beta/;\"\tf"]);
+        assert_eq!(dep_tags_hash(&["!_TAG_RUSTY_TAGS_TIME\t2\t/seconds since the unix epoch/", "beta\tdep/lib.rs\t/^fn beta/;\"\tf"]), hash);
+        assert_ne!(dep_tags_hash(&["!_TAG_RUSTY_TAGS_TIME\t1\t/seconds since the unix epoch/", "gamma\tdep/lib.rs\t/^fn gamma/;\"\tf"]), hash);
+    }
+
+    /// Creates and merges the tags of a crate in 'crate_dir' and checks that its
+    /// source file is tagged with its full path.
+    fn check_tags_of_crate_dir(dir: &Path, crate_dir: &Path) {
+        write_file(crate_dir.join("Cargo.toml"), "[package]\nname = \"spaced\"\nversion = \"0.1.0\"\n");
+        write_file(crate_dir.join("src").join("lib.rs"), "fn spaced() {}\n");
+        assert_eq!(find_dir_upwards_containing("Cargo.toml", &crate_dir.join("src")).unwrap(), crate_dir);
+
+        let config = test_config(dir, &["vi"]);
+        let lib_tags = crate_dir.join("tags dir").join("lib.vi");
+        fs::create_dir_all(lib_tags.parent().unwrap()).unwrap();
+        create_tags(&config, &[crate_dir.join("src")], &lib_tags).unwrap();
+
+        let mut expected = b"spaced\t".to_vec();
+        expected.extend_from_slice(&path_bytes(&crate_dir.join("src").join("lib.rs")));
+        expected.extend_from_slice(b"\t/^fn spaced/;\"\tf");
+
+        let dep_tags = vi_tags_file(dir, "dep.vi", &[]);
+        let mut merged = Vec::new();
+        write_merged_tags(&config, &lib_tags, &[&dep_tags], &mut merged).unwrap();
+        let lines: Vec<_> = merged.split(|b| *b == b'\n').filter(|l| ! l.is_empty() && ! l.starts_with(b"!_")).collect();
+        assert_eq!(lines, vec![&expected[..]]);
+    }
+
+    #[test]
+    fn source_dir_with_a_space() {
+        let dir = test_dir("source_dir_with_a_space");
+        check_tags_of_crate_dir(&dir, &dir.join("my crate"));
+    }
+
+    #[test]
+    fn source_dir_which_is_not_utf8() {
+        let dir = test_dir("source_dir_which_is_not_utf8");
+        check_tags_of_crate_dir(&dir, &dir.join(OsStr::from_bytes(b"cr\xffate")));
+    }
+
+    #[test]
+    fn missing_source_dir_of_a_dependency() {
+        let dir = test_dir("missing_source_dir_of_a_dependency");
+        let (root_dir, dep_dir) = (dir.join("root"), dir.join("dep"));
+        write_file(root_dir.join("Cargo.toml"), "[package]\nname = \"root\"\nversion = \"0.1.0\"\n");
+        write_file(root_dir.join("src").join("lib.rs"), "fn root() {}\n");
+        write_file(dep_dir.join("Cargo.toml"), "[package]\nname = \"dep\"\nversion = \"0.1.0\"\n");
+        write_file(dep_dir.join("src").join("lib.rs"), "fn dep() {}\n");
+
+        let config = test_config(&root_dir, &["vi"]);
+        let mut dep_tree = DepTree::new();
+        let (root, dep, missing) = (dep_tree.new_source(), dep_tree.new_source(), dep_tree.new_source());
+        dep_tree.set_source(test_source(&config, root, "root", &root_dir, true), vec![dep, missing]);
+        dep_tree.set_source(test_source(&config, dep, "dep", &dep_dir, false), vec![]);
+        dep_tree.set_source(test_source(&config, missing, "missing", &dir.join("not-extracted"), false), vec![]);
+        dep_tree.set_roots(vec![root]);
+
+        let summary = update_tags(&config, &dep_tree).unwrap();
+        assert_eq!(summary.missing_sources, 1);
+        assert!(summary.failures.is_empty());
+        assert_eq!(summary.status(), UpdateStatus::MissingSources);
+
+        let tags = fs::read_to_string(root_dir.join("rusty-tags.vi")).unwrap();
+        let names: Vec<_> = tags.lines().filter(|l| ! l.starts_with("!_")).map(|l| l.split('\t').next().unwrap()).collect();
+        assert_eq!(names, vec!["dep", "root"]);
+    }
+
+    #[test]
+    fn unsorted_vi_tags_are_sorted_before_the_merge() {
+        let dir = test_dir("unsorted_vi_tags_are_sorted_before_the_merge");
+        let lib_tags = vi_tags_file(&dir, "lib.vi", &["zeta\tsrc/lib.rs\t/^fn zeta/;\"\tf", "alpha\tsrc/lib.rs\t/^fn alpha/;\"\tf"]);
+        let dep_tags = vi_tags_file(&dir, "dep.vi", &["omega\tdep/lib.rs\t/^fn omega/;\"\tf", "beta\tdep/lib.rs\t/^fn beta/;\"\tf"]);
+
+        let config = test_config(&dir, &["vi"]);
+        let lines = merged_lines(&config, &lib_tags, &[&dep_tags]);
+        let names: Vec<_> = tag_lines(&lines).iter().map(|l| l.split('\t').next().unwrap()).collect();
+        assert_eq!(names, vec!["alpha", "beta", "omega", "zeta"]);
+    }
+
+    /// The peak resident set size of the process in kB.
+    #[cfg(target_os = "linux")]
+    fn peak_rss_kb() -> u64 {
+        let status = fs::read_to_string("/proc/self/status").unwrap();
+        let line = status.lines().find(|l| l.starts_with("VmHWM:")).unwrap();
+        line["VmHWM:".len()..].trim().trim_end_matches("kB").trim().parse().unwrap()
+    }
+
+    // Run by 'cargo test --release -- --ignored --nocapture merge_of_50_files'. Reading
+    // the inputs into memory needed at least their whole size, the streamed merge only
+    // needs its buffers, so the growth of the peak RSS has to stay far below the size.
+    #[cfg(target_os = "linux")]
+    #[test]
+    #[ignore]
+    fn merge_of_50_files_keeps_the_peak_rss_low() {
+        let dir = test_dir("merge_of_50_files_keeps_the_peak_rss_low");
+        let (num_files, num_lines) = (50, 40_000);
+        let files: Vec<_> = (0..num_files).map(|file_idx| {
+            let file = dir.join(format!("{}.vi", file_idx));
+            let mut writer = BufWriter::new(File::create(&file).unwrap());
+            writer.write_all(VI_HEADER.as_bytes()).unwrap();
+            for line_idx in 0..num_lines {
+                writeln!(writer, "name_{:07}_{:02}\tsrc/file_{:02}.rs\t/^fn name_{:07}() {{$/;\"\tf", line_idx, file_idx, file_idx, line_idx).unwrap();
+            }
+
+            file
+        }).collect();
+
+        let input_kb: u64 = files.iter().map(|f| fs::metadata(f).unwrap().len()).sum::<u64>() / 1024;
+        let dep_files: Vec<_> = files[1..].iter().map(PathBuf::as_path).collect();
+        let config = test_config(&dir, &["vi"]);
+
+        let rss_before = peak_rss_kb();
+        let mut writer = BufWriter::new(File::create(dir.join("merged.vi")).unwrap());
+        write_merged_tags(&config, &files[0], &dep_files, &mut writer).unwrap();
+        writer.flush().unwrap();
+        let rss_growth = peak_rss_kb().saturating_sub(rss_before);
+
+        println!("merged {} files of {} kB, peak RSS before: {} kB, growth: {} kB", num_files, input_kb, rss_before, rss_growth);
+        assert!(rss_growth < input_kb / 10, "peak RSS grew by {} kB for {} kB of input", rss_growth, input_kb);
+    }
+}
@@ -0,0 +1,78 @@
+//! Helpers shared by the unit tests.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use semver::Version;
+
+use config::{Action, Config};
+use dirs;
+use types::{Source, SourceId, SourceKind, SourceVersion};
+
+lazy_static! {
+    static ref TEST_ROOT: PathBuf = test_root();
+}
+
+/// The directory of all tests of this process, which contains the rusty-tags
+/// directory of the tests at 'rusty-tags'. The rusty-tags directory can only be
+/// set once per process, so all tests share it.
+fn test_root() -> PathBuf {
+    let root = env::temp_dir().join(format!("rusty-tags-tests-{}", process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("rusty-tags")).unwrap();
+
+    // stops the search of a '.rusty-tags.toml' in the parent directories
+    fs::create_dir_all(root.join(".git")).unwrap();
+
+    dirs::set_rusty_tags_dir(root.join("rusty-tags"));
+    root
+}
+
+/// A new empty directory for the test 'name'.
+pub fn test_dir(name: &str) -> PathBuf {
+    let dir = TEST_ROOT.join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Writes 'contents' into the file 'file', creating its missing parent directories.
+pub fn write_file<P: AsRef<Path>>(file: P, contents: &str) {
+    let file = file.as_ref();
+    if let Some(dir) = file.parent() {
+        fs::create_dir_all(dir).unwrap();
+    }
+
+    fs::write(file, contents).unwrap();
+}
+
+/// The fake universal ctags of the tests, see 'tests/fixtures/bin/ctags'.
+pub fn fake_ctags() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join("bin").join("ctags")
+}
+
+/// The configuration of the command line arguments 'args' for the cargo project
+/// at 'project_dir', with the fake ctags, the rusty-tags directory of the tests
+/// and without the tags of the standard library.
+pub fn test_config(project_dir: &Path, args: &[&str]) -> Config {
+    let project_dir = project_dir.to_string_lossy().into_owned();
+    let ctags = fake_ctags().to_string_lossy().into_owned();
+    let cache_dir = TEST_ROOT.join("rusty-tags").to_string_lossy().into_owned();
+    let mut all_args = vec!["rusty-tags", "--start-dir", &project_dir, "--ctags-exe", &ctags, "--cache-dir", &cache_dir,
+                            "--no-std-lib", "--color", "never", "--quiet"];
+    all_args.extend(args);
+
+    match Action::from_args(all_args) {
+        Ok(Action::UpdateTags(config)) => *config,
+        Ok(_)                          => panic!("Expected the arguments for the tags creation: {:?}", args),
+        Err(err)                       => panic!("Invalid arguments {:?}: {}", args, err)
+    }
+}
+
+/// The path source 'name' with the id 'id' in the directory 'dir'.
+pub fn test_source(config: &Config, id: SourceId, name: &str, dir: &Path, is_root: bool) -> Source {
+    let version = SourceVersion::new(name, Version::parse("0.1.0").unwrap());
+    Source::new(id, &version, dir, SourceKind::Path, is_root, config).unwrap()
+}
@@ -1,16 +1,25 @@
-use std::path::{Path, PathBuf};
-use std::fs::{self, File};
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
-use std::process::Command;
+use std::path::{self, Path, PathBuf};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::hash::Hasher;
+use std::process::{self, Command};
+use std::thread;
+use std::time::{Duration, Instant, UNIX_EPOCH};
 use std::ops::{Drop, Deref};
 use std::fmt;
+use std::ffi::OsStr;
+use std::str::FromStr;
+use std::cmp::Ordering;
 
 use semver::Version;
-use rt_result::RtResult;
-use dirs::{rusty_tags_cache_dir, rusty_tags_locks_dir};
+use rt_result::{RtErr, RtResult};
+use dirs::rusty_tags_locks_dir;
 use config::Config;
+use tags::path_bytes;
 use tempfile::NamedTempFile;
+use glob::Pattern;
+use fnv::{FnvHashSet, FnvHasher};
+use log::debug;
 
 /// The tree describing the dependencies of the whole cargo project.
 #[derive(Debug)]
@@ -30,7 +39,18 @@ pub struct DepTree {
     /// the parents - the dependent sources - of each
     /// source in 'sources', the source ids are indices into
     /// 'sources'
-    parents: Vec<Option<Vec<SourceId>>>
+    parents: Vec<Option<Vec<SourceId>>>,
+
+    /// the dependencies of each source in 'sources' which sources
+    /// couldn't be found, as their names and versions
+    missing_dependencies: Vec<Option<Vec<(String, Version)>>>,
+
+    /// if given, the tags of all roots and of their dependencies are
+    /// written into this single file, instead of a tags file per root
+    workspace_tags_file: Option<PathBuf>,
+
+    /// the number of packages which sources couldn't be found
+    num_missing_sources: usize
 }
 
 impl DepTree {
@@ -39,7 +59,10 @@ impl DepTree {
             roots: Vec::with_capacity(10),
             sources: Vec::new(),
             dependencies: Vec::new(),
-            parents: Vec::new()
+            parents: Vec::new(),
+            missing_dependencies: Vec::new(),
+            workspace_tags_file: None,
+            num_missing_sources: 0
         }
     }
 
@@ -47,28 +70,43 @@ impl DepTree {
         self.sources.reserve(num);
         self.dependencies.reserve(num);
         self.parents.reserve(num);
+        self.missing_dependencies.reserve(num);
     }
 
-    pub fn roots(&self) -> Sources {
+    pub fn roots(&self) -> Sources<'_> {
         Sources::new(&self.sources, Some(&self.roots))
     }
 
-    pub fn dependencies(&self, source: &Source) -> Sources {
+    pub fn dependencies(&self, source: &Source) -> Sources<'_> {
         Sources::new(&self.sources, self.dependencies_slice(source))
     }
 
-    pub fn all_sources<'a>(&'a self) -> Box<Iterator<Item=&Source> + 'a> {
+    pub fn all_sources<'a>(&'a self) -> Box<dyn Iterator<Item=&'a Source> + 'a> {
         Box::new(self.sources
                      .iter()
                      .filter_map(|s| s.as_ref()))
     }
 
-    /// Get all of the ancestors of 'sources' till the roots.
+    /// Get all of the ancestors of 'sources' till the roots. The parents are
+    /// visited by an explicit stack and each source only once, so cyclic
+    /// dependencies terminate and deep trees can't overflow the stack.
     pub fn ancestors<'a>(&'a self, sources: &[&Source]) -> Vec<&'a Source> {
-        let mut ancestor_srcs = Vec::with_capacity(50000);
-        let mut dep_graph = Vec::with_capacity(100);
-        for src in sources {
-            self.ancestors_internal(src, &mut ancestor_srcs, &mut dep_graph);
+        let mut ancestor_srcs = Vec::with_capacity(self.sources.len());
+        let mut visited_ids = FnvHashSet::default();
+        let mut ids_to_visit: Vec<SourceId> = sources.iter().map(|s| s.id).collect();
+        while let Some(id) = ids_to_visit.pop() {
+            if let Some(ref parents) = self.parents[*id] {
+                for p_id in parents {
+                    if ! visited_ids.insert(*p_id) {
+                        continue;
+                    }
+
+                    if let Some(ref p) = self.sources[**p_id] {
+                        ancestor_srcs.push(p);
+                        ids_to_visit.push(*p_id);
+                    }
+                }
+            }
         }
 
         unique_sources(&mut ancestor_srcs);
@@ -81,6 +119,7 @@ impl DepTree {
         self.sources.push(None);
         self.dependencies.push(None);
         self.parents.push(None);
+        self.missing_dependencies.push(None);
         SourceId { id }
     }
 
@@ -88,6 +127,31 @@ impl DepTree {
         self.roots = ids;
     }
 
+    pub fn workspace_tags_file(&self) -> Option<&Path> {
+        self.workspace_tags_file.as_deref()
+    }
+
+    pub fn set_workspace_tags_file(&mut self, file: PathBuf) {
+        self.workspace_tags_file = Some(file);
+    }
+
+    pub fn num_missing_sources(&self) -> usize {
+        self.num_missing_sources
+    }
+
+    pub fn add_missing_source(&mut self) {
+        self.num_missing_sources += 1;
+    }
+
+    /// The direct dependencies of 'source' which sources couldn't be found.
+    pub fn missing_dependencies(&self, source: &Source) -> &[(String, Version)] {
+        self.missing_dependencies[*source.id].as_deref().unwrap_or(&[])
+    }
+
+    pub fn set_missing_dependencies(&mut self, id: SourceId, dependencies: Vec<(String, Version)>) {
+        self.missing_dependencies[*id] = Some(dependencies);
+    }
+
     pub fn set_source(&mut self, src: Source, dependencies: Vec<SourceId>) {
         let src_id = src.id;
         self.sources[*src_id] = Some(src);
@@ -110,29 +174,102 @@ impl DepTree {
     }
 
     fn dependencies_slice(&self, source: &Source) -> Option<&[SourceId]> {
-        self.dependencies[*source.id].as_ref().map(Vec::as_slice)
+        self.dependencies[*source.id].as_deref()
     }
 
-    fn ancestors_internal<'a>(&'a self, source: &Source,
-                              ancestor_srcs: &mut Vec<&'a Source>,
-                              dep_graph: &mut Vec<SourceId>) {
-        dep_graph.push(source.id);
-        if let Some(ref parents) = self.parents[*source.id] {
-            for p_id in parents {
-                // cyclic dependency detected
-                if dep_graph.iter().find(|id| *id == p_id) != None {
-                    continue;
-                }
+}
 
-                if let Some(ref p) = self.sources[**p_id] {
-                    ancestor_srcs.push(p);
-                    self.ancestors_internal(p, ancestor_srcs, dep_graph);
-                }
-            }
+/// How complete the update of the tags was, which
+/// determines the exit code of 'rusty-tags'.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub enum UpdateStatus {
+    /// the tags of all sources were created
+    Complete,
+
+    /// the sources of some packages couldn't be found,
+    /// so the tags were created without them
+    MissingSources,
+
+    /// the tags creation of some sources failed and
+    /// was continued because of '--keep-going'
+    FailedSources
+}
+
+impl UpdateStatus {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            UpdateStatus::Complete       => 0,
+            UpdateStatus::MissingSources => 2,
+            UpdateStatus::FailedSources  => 3
         }
+    }
+}
+
+/// The summary of the tags creation of one kind, printed by '--summary-json'.
+#[derive(Serialize, Debug)]
+pub struct TagsSummary {
+    /// the kind of the created tags
+    pub kind: String,
+
+    /// the cargo project roots
+    pub roots: Vec<RootSummary>,
 
-        dep_graph.pop();
+    /// the number of dependencies which sources couldn't be found
+    pub missing_sources: usize,
+
+    /// the sources which tags were up to date, so they weren't recreated
+    pub up_to_date: Vec<String>,
+
+    /// the sources which tags creation failed while '--keep-going'
+    pub failures: Vec<FailureSummary>,
+
+    /// with '--no-merge' the separate tags files of the standard
+    /// library, of the sources and the extra tags files
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tag_files: Vec<PathBuf>
+}
+
+impl TagsSummary {
+    /// A summary without any created tags.
+    pub fn new(kind: TagsKind) -> TagsSummary {
+        TagsSummary {
+            kind: kind.to_string(),
+            roots: Vec::new(),
+            missing_sources: 0,
+            up_to_date: Vec::new(),
+            failures: Vec::new(),
+            tag_files: Vec::new()
+        }
     }
+
+    pub fn status(&self) -> UpdateStatus {
+        if ! self.failures.is_empty() {
+            UpdateStatus::FailedSources
+        } else if self.missing_sources > 0 {
+            UpdateStatus::MissingSources
+        } else {
+            UpdateStatus::Complete
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct RootSummary {
+    pub name: String,
+    pub version: String,
+    pub src_dir: PathBuf,
+
+    /// the file the tags were written to, none if written to stdout
+    pub tags_file: Option<PathBuf>,
+
+    /// the number of tags in 'tags_file', none for databases
+    pub num_tags: Option<usize>
+}
+
+#[derive(Serialize, Debug)]
+pub struct FailureSummary {
+    pub source: String,
+    pub error: String
 }
 
 /// An iterator over sources by their source ids.
@@ -188,6 +325,9 @@ pub enum SourceLock {
     /// this running instance of 'rusty-tags' holds the lock
     Locked {
         path: PathBuf,
+
+        /// the lock file is kept open as long as the lock is held
+        #[allow(dead_code)]
         file: File
     },
 
@@ -200,7 +340,7 @@ pub enum SourceLock {
 
 impl SourceLock {
     fn new(source: &Source, tags_spec: &TagsSpec) -> RtResult<SourceLock> {
-        let file_name = format!("{}-{}.{}", source.name, source.hash, tags_spec.file_extension());
+        let file_name = format!("{}.{}", source.cache_key, tags_spec.file_extension());
         let lock_file = rusty_tags_locks_dir()?.join(file_name);
         if lock_file.is_file() {
             Ok(SourceLock::AlreadyLocked { path: lock_file })
@@ -218,7 +358,7 @@ impl Drop for SourceLock {
         match *self {
             SourceLock::Locked { ref path, .. } => {
                 if path.is_file() {
-                    let _ = fs::remove_file(&path);
+                    let _ = fs::remove_file(path);
                 }
             }
 
@@ -227,6 +367,95 @@ impl Drop for SourceLock {
     }
 }
 
+/// Lock of the cache directory, which is held while a running instance of
+/// 'rusty-tags' updates the tags, so that multiple instances - e.g. one in
+/// watch mode and a manual run - don't update the cached tags at once.
+/// The lock file contains the process id of the holding instance.
+pub struct CacheLock {
+    path: PathBuf
+}
+
+impl CacheLock {
+    /// Acquires the lock, waiting up to '--lock-timeout' for another instance
+    /// holding it. None if locking is disabled by '--no-lock'.
+    pub fn acquire(config: &Config) -> RtResult<Option<CacheLock>> {
+        if config.no_lock {
+            return Ok(None);
+        }
+
+        let path = cache_lock_path()?;
+        let start = Instant::now();
+        let mut is_waiting = false;
+        loop {
+            if let Some(lock) = CacheLock::try_acquire()? {
+                return Ok(Some(lock));
+            }
+
+            if start.elapsed() >= config.lock_timeout {
+                return Err(format!("Another rusty-tags is updating the cached tags for longer than {} seconds. \
+                                    If this isn't the case remove the lock file '{}', or use '--no-lock'.",
+                                   config.lock_timeout.as_secs(), path.display()).into());
+            }
+
+            if ! is_waiting {
+                info!(config, "Waiting for another rusty-tags updating the cached tags ...");
+                is_waiting = true;
+            }
+
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    /// Acquires the lock without waiting, None if another instance is holding it.
+    pub fn try_acquire() -> RtResult<Option<CacheLock>> {
+        let path = cache_lock_path()?;
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    write!(file, "{}", process::id())?;
+                    return Ok(Some(CacheLock { path }));
+                }
+
+                Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(err) => return Err(err.into())
+            }
+
+            if ! is_stale_cache_lock(&path) {
+                return Ok(None);
+            }
+
+            debug!("Removing the stale lock '{}'", path.display());
+            let _ = fs::remove_file(&path);
+        }
+    }
+}
+
+/// The lock file held while the cached tags are updated.
+fn cache_lock_path() -> RtResult<PathBuf> {
+    Ok(rusty_tags_locks_dir()?.join("cache.lock"))
+}
+
+impl Drop for CacheLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// If the process holding the lock 'path' doesn't exist anymore, e.g. because it was
+/// killed. This can only be detected on linux, elsewhere a lock is never stale.
+fn is_stale_cache_lock(path: &Path) -> bool {
+    if ! cfg!(target_os = "linux") {
+        return false;
+    }
+
+    match fs::read_to_string(path).ok().and_then(|pid| pid.trim().parse::<u32>().ok()) {
+        Some(pid) => ! Path::new("/proc").join(pid.to_string()).exists(),
+
+        // the holding process might not have written its id yet
+        None => false
+    }
+}
+
 #[derive(Debug)]
 pub struct Source {
     /// rusty-tags specific internal id of the source
@@ -241,15 +470,19 @@ pub struct Source {
     /// the root source directory
     pub dir: PathBuf,
 
-    /// hash of 'dir'
-    pub hash: String,
+    /// the stem of the cached files of the source, see 'cache_key'
+    pub cache_key: String,
+
+    /// where the source comes from
+    pub kind: SourceKind,
 
     /// if the source is a root of the dependency tree,
     /// which means that it's a workspace member
     pub is_root: bool,
 
     /// path to the tags file in the source directory,
-    /// beside of the 'Cargo.toml' file, this tags file
+    /// beside of the 'Cargo.toml' file, or for roots in
+    /// the '--output-dir' if given, this tags file
     /// contains of the tags of the source and of its
     /// dependencies
     pub tags_file: PathBuf,
@@ -259,45 +492,177 @@ pub struct Source {
     /// only the tags of the dependencies that have a public
     /// export from the source
     pub cached_tags_file: PathBuf,
+
+    /// path to the file in the rusty-tags cache directory containing
+    /// the 'files_hash' of the last tags creation of the source
+    pub files_hash_file: PathBuf,
 }
 
 impl Source {
-    pub fn new(id: SourceId, source_version: &SourceVersion, dir: &Path, is_root: bool, config: &Config) -> RtResult<Source> {
-        let tags_dir = find_dir_upwards_containing("Cargo.toml", dir).unwrap_or(dir.to_path_buf());
-        let tags_file = tags_dir.join(config.tags_spec.file_name());
-        let hash = source_hash(dir);
+    pub fn new(id: SourceId,
+               source_version: &SourceVersion,
+               dir: &Path,
+               kind: SourceKind,
+               is_root: bool,
+               config: &Config)
+               -> RtResult<Source> {
+        let tags_file = match config.output_dir {
+            // a custom output directory might contain the tags files of multiple
+            // roots, so the name of the source is prepended to the file name
+            // 'gtags' creates multiple files with fixed names, so each root gets its own directory
+            Some(ref output_dir) if is_root && config.tags_spec.kind == TagsKind::Gtags => {
+                output_dir.join(source_version.name).join(config.tags_spec.file_name())
+            }
+
+            Some(ref output_dir) if is_root => {
+                output_dir.join(format!("{}-{}", source_version.name, config.root_tags_file_name()))
+            }
+
+            _ if is_root => {
+                let tags_dir = find_dir_upwards_containing("Cargo.toml", dir).unwrap_or(dir.to_path_buf());
+                tags_dir.join(config.root_tags_file_name())
+            }
+
+            _ => {
+                let tags_dir = find_dir_upwards_containing("Cargo.toml", dir).unwrap_or(dir.to_path_buf());
+                tags_dir.join(config.tags_spec.file_name())
+            }
+        };
+
+        let cache_key = cache_key(source_version, dir);
         let cached_tags_file = {
-            let cache_dir = rusty_tags_cache_dir()?;
-            let file_name = format!("{}-{}.{}", source_version.name, hash, config.tags_spec.file_extension());
+            let cache_dir = config.artifacts_dir()?;
+            let file_name = format!("{}.{}", cache_key, config.tags_spec.file_extension());
             cache_dir.join(&file_name)
         };
 
+        let files_hash_file = cached_tags_file.with_extension(format!("{}.hash", config.tags_spec.file_extension()));
+
         Ok(Source {
-            id: id,
+            id,
             name: source_version.name.to_owned(),
             version: source_version.version.clone(),
             dir: dir.to_owned(),
-            hash: hash,
-            is_root: is_root,
-            tags_file: tags_file,
-            cached_tags_file: cached_tags_file
+            cache_key,
+            kind,
+            is_root,
+            tags_file,
+            cached_tags_file,
+            files_hash_file
         })
     }
 
-    pub fn needs_tags_update(&self, config: &Config) -> bool {
+    pub fn needs_tags_update(&self, config: &Config, dep_tree: &DepTree) -> bool {
         if config.force_recreate {
             return true;
         }
 
-        // Tags of the root (the cargo project) should be always recreated,
-        // because we don't know which source file has been changed and
-        // even if we would know it, we couldn't easily just replace the
-        // tags of the changed source file.
-        if self.is_root {
+        // the tags of the roots aren't stored anywhere if they're written to stdout
+        if self.is_root && config.tags_to_stdout {
             return true;
         }
 
-        ! self.cached_tags_file.is_file() || ! self.tags_file.is_file()
+        let needs_tags_file = ! (self.is_root && config.workspace_tags) && config.single_file.is_none() && ! config.no_merge;
+        if ! self.cached_tags_file.is_file() || (needs_tags_file && ! self.tags_file.is_file()) {
+            return true;
+        }
+
+        // with '--since' git reports the changed files of the editable sources
+        if let (Some(changed_files), true) = (config.changed_since.as_ref(), self.is_editable()) {
+            return self.src_dirs(config).iter().any(|dir| {
+                let dir = fs::canonicalize(dir).unwrap_or_else(|_| dir.clone());
+                changed_files.iter().any(|file| file.starts_with(&dir))
+            });
+        }
+
+        // The roots (the cargo project) and the path dependencies are most
+        // likely edited, so their tags are recreated if any of their source
+        // files changed. The sources of registries and git repositories never
+        // change, so their tags are only recreated if the ctags command changed,
+        // e.g. by other '--ctags-option's.
+        match self.files_hash(config, dep_tree) {
+            Some(hash) => self.stored_files_hash().as_ref() != Some(&hash),
+            None       => true
+        }
+    }
+
+    /// The directories which are tagged for the source. Besides of 'dir' these are
+    /// for roots the existing directories of 'local_target_dirs', which aren't
+    /// already part of 'dir', e.g. 'src/bin' is part of 'src'.
+    pub fn src_dirs(&self, config: &Config) -> Vec<PathBuf> {
+        let mut dirs = vec![self.dir.clone()];
+        if ! self.is_root || config.local_target_dirs.is_empty() {
+            return dirs;
+        }
+
+        let manifest_dir = find_dir_upwards_containing("Cargo.toml", &self.dir).unwrap_or(self.dir.clone());
+        for target_dir in &config.local_target_dirs {
+            let dir = manifest_dir.join(target_dir);
+            if dir.is_dir() && ! dir.starts_with(&self.dir) {
+                dirs.push(dir);
+            }
+        }
+
+        dirs
+    }
+
+    /// If the source files of the source might be edited.
+    pub fn is_editable(&self) -> bool {
+        self.is_root || self.kind == SourceKind::Path
+    }
+
+    /// Hash of the rust source files of the source, of the ctags command and
+    /// of the cached tags files of the dependencies. For sources which aren't
+    /// editable only the ctags command is hashed. By default the paths and
+    /// modification times of the source files are hashed and with '--strict-hash'
+    /// their contents.
+    pub fn files_hash(&self, config: &Config, dep_tree: &DepTree) -> Option<String> {
+        if ! self.is_editable() {
+            return Some(ctags_command_hash(&config.tags_spec));
+        }
+
+        let mut hasher = FnvHasher::default();
+        hash_command(&config.tags_spec.ctags_command(), &mut hasher);
+
+        let mut dep_tags_files: Vec<_> = dep_tree.dependencies(self).map(|d| &d.cached_tags_file).collect();
+        dep_tags_files.sort();
+        for file in dep_tags_files {
+            hash_path(file, &mut hasher);
+        }
+
+        if self.is_root {
+            for file in &config.extra_tags_files {
+                hash_path(file, &mut hasher);
+                hash_modified(file, &mut hasher).ok()?;
+            }
+        }
+
+        for dir in self.src_dirs(config) {
+            hash_rust_files(config, &dir, &mut hasher).ok()?;
+        }
+
+        Some(hasher.finish().to_string())
+    }
+
+    /// The 'files_hash' of the last tags creation of the source.
+    fn stored_files_hash(&self) -> Option<String> {
+        fs::read_to_string(&self.files_hash_file).ok()
+    }
+
+    /// Stores 'hash' as the 'files_hash' of the current tags creation.
+    pub fn store_files_hash(&self, hash: &str) -> RtResult<()> {
+        fs::write(&self.files_hash_file, hash)?;
+        Ok(())
+    }
+
+    /// Removes the stored 'files_hash', so that the tags are recreated
+    /// by the next run if the current tags creation doesn't finish.
+    pub fn remove_files_hash(&self) -> RtResult<()> {
+        if self.files_hash_file.is_file() {
+            fs::remove_file(&self.files_hash_file)?;
+        }
+
+        Ok(())
     }
 
     pub fn recreate_status(&self, config: &Config) -> String {
@@ -305,12 +670,16 @@ impl Source {
             format!("Forced recreating of tags for {}", self.source_version())
         } else if self.is_root {
             format!("Recreating tags for cargo project root {}", self.source_version())
+        } else if self.kind == SourceKind::Path && self.cached_tags_file.is_file() && self.tags_file.is_file() {
+            format!("Recreating tags for path dependency {}, because of changed source files", self.source_version())
         } else if ! self.cached_tags_file.is_file() {
             format!("Recreating tags for {}, because of missing cache file at '{:?}'",
                      self.source_version(), self.cached_tags_file)
         } else if ! self.tags_file.is_file() {
             format!("Recreating tags for {}, because of missing tags file at '{:?}'",
                      self.source_version(), self.tags_file)
+        } else if ! self.is_editable() && self.stored_files_hash() != Some(ctags_command_hash(&config.tags_spec)) {
+            format!("Recreating tags for {}, because of a changed ctags command", self.source_version())
         } else {
             format!("Recreating tags for {}, because one of its dependencies was updated",
                     self.source_version())
@@ -321,7 +690,7 @@ impl Source {
         SourceLock::new(self, tags_spec)
     }
 
-    fn source_version(&self) -> String {
+    pub fn source_version(&self) -> String {
         format!("({}, {})", self.name, self.version)
     }
 }
@@ -335,13 +704,18 @@ pub struct SourceWithTmpTags<'a> {
 
     /// temporary file for the tags of the source
     pub tags_file: NamedTempFile,
+
+    /// the 'files_hash' of the source before its tags are created,
+    /// which is stored after a successful tags creation
+    pub files_hash: Option<String>
 }
 
 impl<'a> SourceWithTmpTags<'a> {
-    pub fn new(source: &'a Source) -> RtResult<SourceWithTmpTags<'a>> {
+    pub fn new(config: &Config, dep_tree: &DepTree, source: &'a Source) -> RtResult<SourceWithTmpTags<'a>> {
         Ok(SourceWithTmpTags {
             source,
-            tags_file: NamedTempFile::new()?
+            tags_file: NamedTempFile::new()?,
+            files_hash: source.files_hash(config, dep_tree)
         })
     }
 }
@@ -361,6 +735,33 @@ impl Deref for SourceId {
     }
 }
 
+/// Where the source of a package comes from, read from
+/// the 'source' entry of the package in 'cargo metadata'.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum SourceKind {
+    /// a package from a registry, like 'crates.io'
+    Registry,
+
+    /// a package checked out from a git repository
+    Git,
+
+    /// a local package referenced by its path
+    Path
+}
+
+impl SourceKind {
+    /// Creates the kind from the 'source' entry of 'cargo metadata',
+    /// which is 'null' for local packages.
+    pub fn from_metadata(source: Option<&str>) -> SourceKind {
+        match source {
+            None                                  => SourceKind::Path,
+            Some(src) if src.starts_with("git+")  => SourceKind::Git,
+            Some(src) if src.starts_with("path+") => SourceKind::Path,
+            Some(_)                               => SourceKind::Registry
+        }
+    }
+}
+
 /// A temporary struct used for the reading of the result of 'cargo metadata'.
 #[derive(PartialEq, Eq, Clone, PartialOrd, Ord, Hash)]
 pub struct SourceVersion<'a> {
@@ -375,25 +776,6 @@ impl<'a> SourceVersion<'a> {
     pub fn new(name: &'a str, version: Version) -> SourceVersion<'a> {
         SourceVersion { name, version }
     }
-
-    /// Parses an id from 'cargo metadata' (e.g "dtoa 0.4.2 (registry+https://github.com/rust-lang/crates.io-index)")
-    /// into a 'SourceVersion'.
-    pub fn parse_from_id(id: &'a str) -> RtResult<SourceVersion<'a>> {
-        let mut split = id.split(' ');
-        let name = split.next();
-        if name == None {
-            return Err(format!("Couldn't extract name from id: '{}'", id).into());
-        }
-        let name = name.unwrap();
-
-        let version = split.next();
-        if version == None {
-            return Err(format!("Couldn't extract version from id: '{}'", id).into());
-        }
-        let version = version.unwrap();
-
-        Ok(SourceVersion::new(name, Version::parse(version)?))
-    }
 }
 
 impl<'a> fmt::Debug for SourceVersion<'a> {
@@ -408,32 +790,194 @@ impl<'a> fmt::Display for SourceVersion<'a> {
     }
 }
 
-fn source_hash(source_dir: &Path) -> String {
-    let mut hasher = DefaultHasher::new();
-    source_dir.hash(&mut hasher);
-    hasher.finish().to_string()
+/// The stem of the cached files of the source 'source_version' at 'source_dir', like
+/// 'serde-1.0.0-1b2d9c0e6f3a4d57'. Besides of the name and the version it contains the
+/// hash of the absolute 'source_dir', so that the cached files of sources with the same
+/// version - e.g. path dependencies of different projects - don't collide. Characters
+/// which might not be valid in file names are replaced by '_'.
+///
+/// The hash is the 64 bit FNV-1a hash of the bytes of the path, which - unlike the
+/// 'DefaultHasher' - doesn't change between rust releases, otherwise every toolchain
+/// update would orphan the whole cache.
+pub fn cache_key(source_version: &SourceVersion, source_dir: &Path) -> String {
+    let mut hasher = FnvHasher::default();
+    hasher.write(&path_bytes(&path::absolute(source_dir).unwrap_or_else(|_| source_dir.to_path_buf())));
+
+    let key = format!("{}-{}-{:016x}", source_version.name, source_version.version, hasher.finish());
+    key.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '_' })
+        .collect()
 }
 
 /// which kind of tags are created
-arg_enum! {
-    #[derive(Eq, PartialEq, Debug)]
-    pub enum TagsKind {
-        Vi,
-        Emacs
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum TagsKind {
+    Vi,
+    Emacs,
+    Gtags,
+    Cscope
+}
+
+/// the accepted names of the tags kinds, ignoring case
+const TAGS_KIND_NAMES: &[(&str, TagsKind)] = &[
+    ("vi", TagsKind::Vi),
+    ("vim", TagsKind::Vi),
+    ("ctags", TagsKind::Vi),
+    ("emacs", TagsKind::Emacs),
+    ("etags", TagsKind::Emacs),
+    ("gtags", TagsKind::Gtags),
+    ("cscope", TagsKind::Cscope)
+];
+
+impl TagsKind {
+    /// the accepted names of all kinds
+    pub fn names() -> Vec<&'static str> {
+        TAGS_KIND_NAMES.iter().map(|&(name, _)| name).collect()
     }
 }
 
+impl FromStr for TagsKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<TagsKind, String> {
+        let name = s.to_lowercase();
+        TAGS_KIND_NAMES.iter()
+            .find(|&&(n, _)| n == name)
+            .map(|&(_, kind)| kind)
+            .ok_or_else(|| format!("Invalid tags kind '{}', valid values are: {}", s, TagsKind::names().join(", ")))
+    }
+}
+
+impl fmt::Display for TagsKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            TagsKind::Vi     => "vi",
+            TagsKind::Emacs  => "emacs",
+            TagsKind::Gtags  => "gtags",
+            TagsKind::Cscope => "cscope"
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl TagsKind {
+    /// if the tags are a cross reference database, which
+    /// can't be merged with the tags of other sources
+    pub fn is_database(self) -> bool {
+        match self {
+            TagsKind::Vi | TagsKind::Emacs     => false,
+            TagsKind::Gtags | TagsKind::Cscope => true
+        }
+    }
+
+    pub fn file_extension(self) -> &'static str {
+        match self {
+            TagsKind::Vi     => "vi",
+            TagsKind::Emacs  => "emacs",
+            TagsKind::Gtags  => "gtags",
+            TagsKind::Cscope => "cscope"
+        }
+    }
+}
+
+/// how the lines of vi tags files are sorted, like the '--sort' option of ctags
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum TagsSort {
+    /// sorted by the bytes of the lines, the default of ctags
+    Yes,
+
+    /// sorted ignoring the case of ascii letters, for vim with 'ignorecase'
+    Foldcase,
+
+    /// not sorted at all
+    No
+}
+
+impl TagsSort {
+    pub fn names() -> &'static [&'static str] {
+        &["yes", "foldcase", "no"]
+    }
+
+    /// the ctags option creating tags with this sorting
+    pub fn ctags_option(self) -> &'static str {
+        match self {
+            TagsSort::Yes      => "--sort=yes",
+            TagsSort::Foldcase => "--sort=foldcase",
+            TagsSort::No       => "--sort=no"
+        }
+    }
+
+    /// the value of the '!_TAG_FILE_SORTED' pseudo tag
+    pub fn pseudo_tag_value(self) -> u8 {
+        match self {
+            TagsSort::No       => 0,
+            TagsSort::Yes      => 1,
+            TagsSort::Foldcase => 2
+        }
+    }
+
+    /// Compares the lines 'a' and 'b' of vi tags files by this sorting, lines which
+    /// only differ by case are ordered by their bytes. Unsorted lines are treated
+    /// like sorted by their bytes, so they can still be merged.
+    pub fn compare(self, a: &[u8], b: &[u8]) -> Ordering {
+        match self {
+            TagsSort::Yes | TagsSort::No => a.cmp(b),
+            TagsSort::Foldcase           => {
+                a.iter().map(u8::to_ascii_lowercase).cmp(b.iter().map(u8::to_ascii_lowercase))
+                    .then_with(|| a.cmp(b))
+            }
+        }
+    }
+
+    /// If the lines 'a' and 'b' are equal for this sorting, before the lines only
+    /// differing by case are ordered by their bytes. All lines between equivalent
+    /// lines of sorted tags are equivalent to them.
+    pub fn is_equivalent(self, a: &[u8], b: &[u8]) -> bool {
+        match self {
+            TagsSort::Yes | TagsSort::No => a == b,
+            TagsSort::Foldcase           => a.eq_ignore_ascii_case(b)
+        }
+    }
+}
+
+impl FromStr for TagsSort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<TagsSort, String> {
+        match s.to_lowercase().as_str() {
+            "yes"      => Ok(TagsSort::Yes),
+            "foldcase" => Ok(TagsSort::Foldcase),
+            "no"       => Ok(TagsSort::No),
+            _          => Err(format!("Invalid sorting '{}', valid values are: {}", s, TagsSort::names().join(", ")))
+        }
+    }
+}
+
+/// the file name of the GNU Global database, beside of it 'gtags'
+/// creates the files 'GRTAGS' and 'GPATH'
+pub const GTAGS_FILE_NAME: &str = "GTAGS";
+
+/// the file name of the cscope database
+pub const CSCOPE_FILE_NAME: &str = "cscope.out";
+
+/// the file name of the list of the source files given to cscope
+pub const CSCOPE_FILES_LIST_NAME: &str = "cscope.files";
+
 type ExeName = String;
 
-/// which ctags executable is used
-#[derive(Debug)]
+/// which ctags executable is used, or the 'gtags' executable for GNU Global databases
+#[derive(Debug, Clone)]
 pub enum TagsExe {
     ExuberantCtags(ExeName),
-    UniversalCtags(ExeName)
+    UniversalCtags(ExeName),
+    GnuGlobal(ExeName),
+    Cscope(ExeName)
 }
 
 /// holds additional info for the kind of tags, which extension
 /// they use for caching and which user viewable file names they get
+#[derive(Clone)]
 pub struct TagsSpec {
     pub kind: TagsKind,
 
@@ -446,45 +990,141 @@ pub struct TagsSpec {
     emacs_tags: String,
 
     /// options given to the ctags executable
-    ctags_options: String
+    ctags_options: String,
+
+    /// options given by '--ctags-option', they're given after all other
+    /// options to the ctags executable, so they can override them
+    extra_ctags_options: Vec<String>,
+
+    /// glob patterns of the files and directories excluded from tagging
+    excludes: Vec<Pattern>,
+
+    /// the ctags options file with the Rust definitions of '--rust-regex',
+    /// which replace the default ones of exuberant ctags
+    rust_regex_file: Option<PathBuf>,
+
+    /// let ctags parse the files of all languages, not only the rust files
+    all_languages: bool,
+
+    /// the first line of the '--version' output of the ctags executable
+    exe_version: Option<String>
 }
 
 impl TagsSpec {
-    pub fn new(kind: TagsKind, exe: TagsExe, vi_tags: String, emacs_tags: String, ctags_options: String) -> RtResult<TagsSpec> {
+    pub fn new(kind: TagsKind, exe: TagsExe, vi_tags: String, emacs_tags: String,
+               ctags_options: String, extra_ctags_options: Vec<String>, excludes: Vec<String>) -> RtResult<TagsSpec> {
         if vi_tags == emacs_tags {
             return Err(format!("It's not supported to use the same tags name '{}' for vi and emacs!", vi_tags).into());
         }
 
+        let excludes = {
+            let mut patterns = Vec::with_capacity(excludes.len());
+            for exclude in &excludes {
+                patterns.push(Pattern::new(exclude)
+                    .map_err(|err| format!("Invalid exclude pattern '{}': {}", exclude, err))?);
+            }
+
+            patterns
+        };
+
         Ok(TagsSpec {
-            kind: kind,
-            exe: exe,
-            vi_tags: vi_tags,
-            emacs_tags: emacs_tags,
-            ctags_options: ctags_options
+            kind,
+            exe,
+            vi_tags,
+            emacs_tags,
+            ctags_options,
+            extra_ctags_options,
+            excludes,
+            rust_regex_file: None,
+            all_languages: false,
+            exe_version: None
         })
     }
 
+    /// Uses the Rust definitions of 'file' for exuberant ctags.
+    pub fn set_rust_regex_file(&mut self, file: PathBuf) {
+        self.rust_regex_file = Some(file);
+    }
+
+    /// Lets ctags parse the files of all languages it knows, not only the rust files.
+    pub fn set_all_languages(&mut self) {
+        self.all_languages = true;
+    }
+
+    /// Sets the version of the ctags executable, which is recorded in the merged vi tags.
+    pub fn set_exe_version(&mut self, version: String) {
+        self.exe_version = Some(version);
+    }
+
+    /// The version of the ctags executable, if it could be read.
+    pub fn exe_version(&self) -> Option<&str> {
+        self.exe_version.as_deref()
+    }
+
     pub fn file_extension(&self) -> &'static str {
-        match self.kind {
-            TagsKind::Vi    => "vi",
-            TagsKind::Emacs => "emacs"
-        }
+        self.kind.file_extension()
     }
 
     pub fn file_name(&self) -> &str {
         match self.kind {
             TagsKind::Vi    => &self.vi_tags,
-            TagsKind::Emacs => &self.emacs_tags
+            TagsKind::Emacs => &self.emacs_tags,
+            TagsKind::Gtags  => GTAGS_FILE_NAME,
+            TagsKind::Cscope => CSCOPE_FILE_NAME
         }
     }
 
+    /// the name or path of the ctags executable
+    pub fn exe_name(&self) -> &str {
+        match self.exe {
+            TagsExe::ExuberantCtags(ref exe_name)
+                | TagsExe::UniversalCtags(ref exe_name)
+                | TagsExe::GnuGlobal(ref exe_name)
+                | TagsExe::Cscope(ref exe_name) => exe_name
+        }
+    }
+
+    /// if 'path' matches any of the exclude patterns, either by
+    /// its file name or by its whole path, like ctags does it
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        let file_name = path.file_name().map(Path::new);
+        self.excludes.iter().any(|pattern| {
+            pattern.matches_path(path) || file_name.is_some_and(|name| pattern.matches_path(name))
+        })
+    }
+
     pub fn ctags_command(&self) -> Command {
+        let mut cmd = self.rusty_tags_ctags_command();
+
+        // 'gtags' and 'cscope' get the already filtered source files
+        if self.kind.is_database() {
+            return cmd;
+        }
+
+        for exclude in &self.excludes {
+            cmd.arg(format!("--exclude={}", exclude));
+        }
+
+        for option in &self.extra_ctags_options {
+            cmd.arg(option);
+        }
+
+        cmd
+    }
+
+    /// the ctags command with only the options of rusty-tags and the config file
+    fn rusty_tags_ctags_command(&self) -> Command {
         match self.exe {
             TagsExe::ExuberantCtags(ref exe_name) => {
-                let mut cmd = Command::new(&exe_name);
+                let mut cmd = Command::new(exe_name);
                 self.generic_ctags_options(&mut cmd);
-                cmd.arg("--languages=Rust")
-                   .arg("--langdef=Rust")
+                if let Some(ref file) = self.rust_regex_file {
+                    cmd.arg(format!("--options={}", file.display()));
+                    self.languages_option(&mut cmd);
+                    return cmd;
+                }
+
+                cmd.arg("--langdef=Rust")
                    .arg("--langmap=Rust:.rs")
                    .arg("--regex-Rust=/^[ \\t]*(#\\[[^\\]]\\][ \\t]*)*(pub[ \\t]+)?(extern[ \\t]+)?(\"[^\"]+\"[ \\t]+)?(unsafe[ \\t]+)?fn[ \\t]+([a-zA-Z0-9_]+)/\\6/f,functions,function definitions/")
                    .arg("--regex-Rust=/^[ \\t]*(pub[ \\t]+)?type[ \\t]+([a-zA-Z0-9_]+)/\\2/T,types,type definitions/")
@@ -495,23 +1135,35 @@ impl TagsSpec {
                    .arg("--regex-Rust=/^[ \\t]*(pub[ \\t]+)?(unsafe[ \\t]+)?trait[ \\t]+([a-zA-Z0-9_]+)/\\3/t,traits,traits/")
                    .arg("--regex-Rust=/^[ \\t]*macro_rules![ \\t]+([a-zA-Z0-9_]+)/\\1/d,macros,macro definitions/");
 
+                self.languages_option(&mut cmd);
                 cmd
             }
 
             TagsExe::UniversalCtags(ref exe_name) => {
-                let mut cmd = Command::new(&exe_name);
+                let mut cmd = Command::new(exe_name);
                 self.generic_ctags_options(&mut cmd);
-                cmd.arg("--languages=Rust");
+                self.languages_option(&mut cmd);
 
                 cmd
             }
+
+            TagsExe::GnuGlobal(ref exe_name) | TagsExe::Cscope(ref exe_name) => Command::new(exe_name)
+        }
+    }
+
+    /// Restricts ctags to the rust files. Exuberant ctags only knows the language 'Rust'
+    /// after its definition, so this has to follow '--langdef=Rust' or the options file
+    /// with the definition.
+    fn languages_option(&self, cmd: &mut Command) {
+        if ! self.all_languages {
+            cmd.arg("--languages=Rust");
         }
     }
 
     fn generic_ctags_options(&self, cmd: &mut Command) {
         match self.kind {
-            TagsKind::Vi    => {}
-            TagsKind::Emacs => { cmd.arg("-e"); }
+            TagsKind::Vi | TagsKind::Gtags | TagsKind::Cscope => {}
+            TagsKind::Emacs                                  => { cmd.arg("-e"); }
         }
 
         cmd.arg("--recurse");
@@ -521,20 +1173,110 @@ impl TagsSpec {
     }
 }
 
+/// The hash of the ctags command of 'tags_spec', to detect tags
+/// created with different options.
+pub fn ctags_command_hash(tags_spec: &TagsSpec) -> String {
+    let mut hasher = FnvHasher::default();
+    hash_command(&tags_spec.ctags_command(), &mut hasher);
+    hasher.finish().to_string()
+}
+
+/// Hashes the program and the arguments of 'cmd'. The stored hashes are compared
+/// across runs - and rust releases - so like the hash of 'cache_key' they're FNV-1a
+/// hashes of bytes and don't depend on the 'Debug' output of the command.
+fn hash_command(cmd: &Command, hasher: &mut FnvHasher) {
+    hash_path(Path::new(cmd.get_program()), hasher);
+    for arg in cmd.get_args() {
+        hash_path(Path::new(arg), hasher);
+    }
+}
+
+/// Hashes the bytes of 'path', followed by a 0 byte, which separates it from
+/// the following hashed bytes.
+fn hash_path(path: &Path, hasher: &mut FnvHasher) {
+    hasher.write(&path_bytes(path));
+    hasher.write_u8(0);
+}
+
+/// Hashes the modification time of the file 'path'.
+fn hash_modified(path: &Path, hasher: &mut FnvHasher) -> io::Result<()> {
+    let modified = fs::metadata(path)?.modified()?;
+    let since_epoch = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+    hasher.write_u64(since_epoch.as_secs());
+    hasher.write_u32(since_epoch.subsec_nanos());
+    Ok(())
+}
+
+/// the directories of version control systems, which are never searched for
+/// source files, like ctags skips them by default
+const VCS_DIRS: &[&str] = &[".git", ".hg", ".svn"];
+
 pub fn unique_sources(sources: &mut Vec<&Source>) {
-    sources.sort_unstable_by(|a, b| a.id.cmp(&b.id));
+    sources.sort_unstable_by_key(|a| a.id);
     sources.dedup_by_key(|s| &s.id);
 }
 
-fn find_dir_upwards_containing(file_name: &str, start_dir: &Path) -> RtResult<PathBuf> {
+/// Hashes the paths and the modification times - or with '--strict-hash' the contents -
+/// of all rust source files in 'dir' and its subdirectories, which aren't excluded.
+fn hash_rust_files(config: &Config, dir: &Path, hasher: &mut FnvHasher) -> io::Result<()> {
+    for path in rust_files(config, dir)? {
+        hash_path(&path, hasher);
+        if config.strict_hash {
+            let contents = fs::read(&path)?;
+            hasher.write_u64(contents.len() as u64);
+            hasher.write(&contents);
+        } else {
+            hash_modified(&path, hasher)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// All rust source files in 'dir' and its subdirectories, which aren't excluded,
+/// skipping hidden directories - which names start with a '.' - unless
+/// '--include-hidden' is given. Sorted by their paths.
+pub fn rust_files(config: &Config, dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    collect_rust_files(config, dir, &mut files)?;
+    return Ok(files);
+
+    fn collect_rust_files(config: &Config, dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+        let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+        entries.sort_by_key(|e| e.path());
+        for entry in entries {
+            let path = entry.path();
+            if config.tags_spec.is_excluded(&path) {
+                continue;
+            }
+
+            if entry.file_type()?.is_dir() {
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                if name.starts_with('.') && (! config.include_hidden || VCS_DIRS.contains(&name.as_ref())) {
+                    continue;
+                }
+
+                collect_rust_files(config, &path, files)?;
+            } else if path.extension().is_some_and(|ext| ext == "rs") {
+                files.push(path);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub fn find_dir_upwards_containing(file_name: &str, start_dir: &Path) -> RtResult<PathBuf> {
     let mut dir = start_dir.to_path_buf();
     loop {
+        debug!("Searching '{}' in '{}'", file_name, dir.display());
         if let Ok(files) = fs::read_dir(&dir) {
             for path in files.map(|r| r.map(|d| d.path())) {
                 match path {
                     Ok(ref path) if path.is_file() =>
                         match path.file_name() {
-                            Some(name) if name.to_str() == Some(file_name) => return Ok(dir),
+                            Some(name) if name == OsStr::new(file_name) => return Ok(dir),
                             _ => continue
                         },
                     _ => continue
@@ -543,7 +1285,156 @@ fn find_dir_upwards_containing(file_name: &str, start_dir: &Path) -> RtResult<Pa
         }
 
         if ! dir.pop() {
-            return Err(format!("Couldn't find '{}' starting at directory '{}'!", file_name, start_dir.display()).into());
+            return Err(RtErr::NotFound { file_name: file_name.to_string(), start_dir: start_dir.to_path_buf() });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::path::{Path, PathBuf};
+
+    use semver::Version;
+
+    #[cfg(unix)]
+    use test_support::{test_config, test_dir, test_source};
+    use super::*;
+
+    fn serde_version() -> SourceVersion<'static> {
+        SourceVersion::new("serde", Version::parse("1.0.0").unwrap())
+    }
+
+    #[test]
+    fn cache_key_differs_for_different_dirs() {
+        let keys = [
+            cache_key(&serde_version(), Path::new("/home/a/serde")),
+            cache_key(&serde_version(), Path::new("/home/b/serde")),
+            cache_key(&serde_version(), Path::new("/home/a/serde-copy")),
+            cache_key(&serde_version(), Path::new("/serde"))
+        ];
+
+        for (idx, key) in keys.iter().enumerate() {
+            assert!(key.starts_with("serde-1.0.0-"), "{}", key);
+            assert!(keys[idx + 1..].iter().all(|k| k != key), "{:?}", keys);
+        }
+    }
+
+    #[test]
+    fn cache_key_is_the_same_for_relative_and_absolute_dir() {
+        let relative = Path::new("some-dir").join("serde");
+        let absolute = env::current_dir().unwrap().join(&relative);
+        assert_eq!(cache_key(&serde_version(), &relative), cache_key(&serde_version(), &absolute));
+    }
+
+    #[test]
+    fn cache_key_replaces_non_file_name_chars() {
+        let version = SourceVersion::new("we/ird:na me", Version::parse("1.0.0+build").unwrap());
+        let key = cache_key(&version, Path::new("/tmp/weird"));
+        assert!(key.starts_with("we_ird_na_me-1.0.0_build-"), "{}", key);
+        assert!(key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.'), "{}", key);
+    }
+
+    #[test]
+    fn tags_kind_from_every_name() {
+        for &(name, kind) in TAGS_KIND_NAMES {
+            assert_eq!(name.parse::<TagsKind>(), Ok(kind), "{}", name);
+            assert_eq!(name.to_uppercase().parse::<TagsKind>(), Ok(kind), "{}", name);
+        }
+
+        assert_eq!("ViM".parse::<TagsKind>(), Ok(TagsKind::Vi));
+        assert_eq!("ETAGS".parse::<TagsKind>(), Ok(TagsKind::Emacs));
+    }
+
+    #[test]
+    fn tags_kind_from_unknown_name() {
+        assert_eq!("nvim".parse::<TagsKind>(),
+                   Err("Invalid tags kind 'nvim', valid values are: vi, vim, ctags, emacs, etags, gtags, cscope".to_string()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ancestors_of_cyclic_dependencies() {
+        let dir = test_dir("ancestors_of_cyclic_dependencies");
+        let config = test_config(&dir, &["vi"]);
+
+        // root -> a -> b -> a
+        let mut dep_tree = DepTree::new();
+        let (root, a, b, other) = (dep_tree.new_source(), dep_tree.new_source(), dep_tree.new_source(), dep_tree.new_source());
+        dep_tree.set_source(test_source(&config, root, "root", &dir, true), vec![a]);
+        dep_tree.set_source(test_source(&config, a, "a", &dir.join("a"), false), vec![b]);
+        dep_tree.set_source(test_source(&config, b, "b", &dir.join("b"), false), vec![a]);
+        dep_tree.set_source(test_source(&config, other, "other", &dir.join("other"), true), vec![]);
+        dep_tree.set_roots(vec![root, other]);
+
+        let ancestor_ids = |src: SourceId| -> Vec<SourceId> {
+            let src = dep_tree.all_sources().find(|s| s.id == src).unwrap();
+            dep_tree.ancestors(&[src]).iter().map(|s| s.id).collect()
+        };
+
+        assert_eq!(ancestor_ids(b), vec![root, a, b]);
+        assert_eq!(ancestor_ids(a), vec![root, a, b]);
+        assert_eq!(ancestor_ids(root), vec![]);
+    }
+
+    #[test]
+    fn extra_ctags_options_come_last() {
+        let tags_spec = TagsSpec::new(TagsKind::Vi, TagsExe::UniversalCtags("ctags".to_string()), "rusty-tags.vi".to_string(),
+                                      "rusty-tags.emacs".to_string(), "--fields=+l".to_string(),
+                                      vec!["--fields=+n".to_string(), "--kinds-Rust=-m".to_string()],
+                                      vec!["target".to_string(), "*.bak".to_string()]).unwrap();
+
+        let cmd = tags_spec.ctags_command();
+        let args: Vec<_> = cmd.get_args().map(|a| a.to_str().unwrap()).collect();
+        assert_eq!(args, vec!["--recurse", "--fields=+l", "--languages=Rust", "--exclude=target", "--exclude=*.bak",
+                              "--fields=+n", "--kinds-Rust=-m"]);
+    }
+
+    fn ctags_args(tags_spec: &TagsSpec) -> Vec<String> {
+        tags_spec.ctags_command().get_args().map(|a| a.to_str().unwrap().to_string()).collect()
+    }
+
+    fn vi_tags_spec(exe: TagsExe) -> TagsSpec {
+        TagsSpec::new(TagsKind::Vi, exe, "rusty-tags.vi".to_string(), "rusty-tags.emacs".to_string(),
+                      String::new(), Vec::new(), Vec::new()).unwrap()
+    }
+
+    #[test]
+    fn languages_option_follows_the_rust_definition() {
+        let args = ctags_args(&vi_tags_spec(TagsExe::ExuberantCtags("ctags".to_string())));
+        let langdef = args.iter().position(|a| a == "--langdef=Rust").unwrap();
+        let last_regex = args.iter().rposition(|a| a.starts_with("--regex-Rust=")).unwrap();
+        assert_eq!(args[0], "--recurse");
+        assert!(langdef < last_regex, "{:?}", args);
+        assert_eq!(args[last_regex + 1..], ["--languages=Rust"]);
+
+        let mut tags_spec = vi_tags_spec(TagsExe::ExuberantCtags("ctags".to_string()));
+        tags_spec.set_rust_regex_file(PathBuf::from("/tmp/rust.ctags"));
+        assert_eq!(ctags_args(&tags_spec), vec!["--recurse", "--options=/tmp/rust.ctags", "--languages=Rust"]);
+
+        assert_eq!(ctags_args(&vi_tags_spec(TagsExe::UniversalCtags("ctags".to_string()))), vec!["--recurse", "--languages=Rust"]);
+    }
+
+    #[test]
+    fn all_languages_drops_the_languages_option() {
+        for exe in [TagsExe::ExuberantCtags("ctags".to_string()), TagsExe::UniversalCtags("ctags".to_string())] {
+            let mut tags_spec = vi_tags_spec(exe);
+            tags_spec.set_all_languages();
+            let args = ctags_args(&tags_spec);
+            assert!(! args.iter().any(|a| a.starts_with("--languages")), "{:?}", args);
         }
     }
+
+    // the hash is part of the names of the cached files, so it must never change
+    #[cfg(unix)]
+    #[test]
+    fn cache_key_hash_is_fnv_1a() {
+        assert_eq!(cache_key(&serde_version(), Path::new("/tmp/serde")), "serde-1.0.0-9e7b564e4621faa1");
+    }
+
+    // the hash is stored with the cached tags, so a change recreates all of them
+    #[test]
+    fn ctags_command_hash_is_fnv_1a() {
+        assert_eq!(ctags_command_hash(&vi_tags_spec(TagsExe::UniversalCtags("ctags".to_string()))), "2638751108817633662");
+    }
 }
@@ -0,0 +1,133 @@
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::time::Duration;
+
+use serde_json;
+use notify::{self, DebouncedEvent, RecursiveMode, Watcher};
+
+use rt_result::RtResult;
+use config::Config;
+use dependencies::dependency_tree;
+use types::{DepTree, SourceId, TagsKind};
+use update_tags_of_kind;
+
+/// The time in which changes of the source files are coalesced, e.g.
+/// while 'cargo fmt' is writing multiple files.
+const DEBOUNCE_TIME: Duration = Duration::from_millis(500);
+
+/// Watches the source directories of the cargo project roots and of their path
+/// dependencies and updates the tags of the roots depending on changed source
+/// files. Registry and git dependencies don't change, so they aren't watched.
+pub fn watch_tags(config: &Config, metadata: &serde_json::Value) -> RtResult<()> {
+    // the tags of the standard library and of the unchanged
+    // dependencies were already recreated by the initial run
+    let config = Config { force_recreate: false, ..config.clone() };
+
+    let (sender, receiver) = channel();
+    let mut watcher = notify::watcher(sender, DEBOUNCE_TIME)
+        .map_err(|err| format!("Couldn't create the watcher of the source files: {}", err))?;
+
+    let watched_dirs: Vec<_> = dependency_tree(&config, metadata)?
+        .all_sources()
+        .filter(|s| s.is_editable())
+        .flat_map(|s| s.src_dirs(&config))
+        .collect();
+
+    for dir in &watched_dirs {
+        verbose!(config, "Watching '{}'", dir.display());
+        watcher.watch(dir, RecursiveMode::Recursive)
+            .map_err(|err| format!("Couldn't watch the directory '{}': {}", dir.display(), err))?;
+    }
+
+    info!(config, "\nWatching for changes of the source files ...");
+
+    loop {
+        // wait for the first change and then collect all changes
+        // till there wasn't any for the debounce time
+        let mut changed_files = Vec::new();
+        let mut event = receiver.recv().map_err(|err| format!("Watching the source files failed: {}", err))?;
+        loop {
+            add_changed_files(&config, event, &watched_dirs, &mut changed_files);
+            event = match receiver.recv_timeout(DEBOUNCE_TIME) {
+                Ok(event)                           => event,
+                Err(RecvTimeoutError::Timeout)      => break,
+                Err(RecvTimeoutError::Disconnected) => return Err("Watching the source files failed!".into())
+            };
+        }
+
+        if changed_files.is_empty() {
+            continue;
+        }
+
+        changed_files.sort();
+        changed_files.dedup();
+        if config.verbose {
+            output!(config.output_to_stderr, "\nChanged source files:");
+            for file in &changed_files {
+                output!(config.output_to_stderr, "   {}", file.display());
+            }
+        }
+
+        // a failed update shouldn't stop the watching
+        for tags_spec in &config.tags_specs {
+            if let Err(err) = update_tags_of_kind(&config.for_tags_spec(tags_spec), metadata, Some(&changed_files)) {
+                eprintln!("{}", err.to_string().trim_end());
+            }
+        }
+
+        info!(config, "\nWatching for changes of the source files ...");
+    }
+}
+
+/// The ids of the roots of 'dep_tree' which tags contain any of 'changed_files', either
+/// because they're part of the root or part of any of its (indirect) dependencies.
+pub fn affected_root_ids(config: &Config, dep_tree: &DepTree, changed_files: &[PathBuf]) -> Vec<SourceId> {
+    // the tags of all roots are combined, so all of them have to be updated
+    if config.workspace_tags || config.tags_spec.kind == TagsKind::Cscope {
+        return dep_tree.roots().map(|r| r.id).collect();
+    }
+
+    let changed_sources: Vec<_> = dep_tree.all_sources()
+        .filter(|s| s.is_editable() && changed_files.iter().any(|f| s.src_dirs(config).iter().any(|d| f.starts_with(d))))
+        .collect();
+
+    let mut affected_sources = dep_tree.ancestors(&changed_sources);
+    affected_sources.extend(changed_sources);
+    dep_tree.roots()
+        .filter(|r| affected_sources.iter().any(|s| s.id == r.id))
+        .map(|r| r.id)
+        .collect()
+}
+
+/// Adds the paths of 'event' to 'changed_files', which are rust source files
+/// and aren't excluded. For a rescan all watched directories are considered
+/// as changed.
+fn add_changed_files(config: &Config, event: DebouncedEvent, watched_dirs: &[PathBuf], changed_files: &mut Vec<PathBuf>) {
+    let paths = match event {
+        DebouncedEvent::Create(path) | DebouncedEvent::Write(path) | DebouncedEvent::Remove(path) => vec![path],
+        DebouncedEvent::Rename(from, to) => vec![from, to],
+        DebouncedEvent::Rescan => {
+            changed_files.extend(watched_dirs.iter().cloned());
+            return;
+        }
+
+        DebouncedEvent::Error(err, path) => {
+            warn!(config, "Error while watching {:?}: {}", path, err);
+            return;
+        }
+
+        _ => return
+    };
+
+    for path in paths {
+        let is_rust_file = path.extension().is_some_and(|ext| ext == "rs");
+        let is_excluded = watched_dirs.iter()
+            .find(|dir| path.starts_with(dir))
+            .map(|dir| path.ancestors().take_while(|p| p != dir).any(|p| config.tags_spec.is_excluded(p)))
+            .unwrap_or(true);
+
+        if is_rust_file && ! is_excluded {
+            changed_files.push(path);
+        }
+    }
+}
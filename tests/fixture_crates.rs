@@ -0,0 +1,223 @@
+//! Runs rusty-tags on the small cargo projects in 'tests/fixtures' with the fake
+//! ctags of 'tests/fixtures/bin/ctags', which only tags the functions.
+
+#![cfg(unix)]
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures")
+}
+
+/// The directory of the test 'name', a new empty one on every call.
+fn test_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("rusty-tags-fixtures-{}", process::id())).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Copies the fixture 'name' into 'dir' and returns the copy. The fixtures are
+/// never used in place, so cargo doesn't write into the repository.
+fn copy_fixture(name: &str, dir: &Path) -> PathBuf {
+    let copy = dir.join(name);
+    copy_dir(&fixtures_dir().join(name), &copy);
+    copy
+}
+
+fn copy_dir(from: &Path, to: &Path) {
+    fs::create_dir_all(to).unwrap();
+    for entry in fs::read_dir(from).unwrap() {
+        let path = entry.unwrap().path();
+        let to_path = to.join(path.file_name().unwrap());
+        if path.is_dir() {
+            copy_dir(&path, &to_path);
+        } else {
+            fs::copy(&path, &to_path).unwrap();
+        }
+    }
+}
+
+/// Runs rusty-tags with 'args' in the cargo project 'project_dir' and with its
+/// cache in 'cache_dir', without the standard library and without fetching.
+fn rusty_tags(project_dir: &Path, cache_dir: &Path, args: &[&str]) -> String {
+    let mut cmd = rusty_tags_command(project_dir, cache_dir);
+    cmd.arg("--no-std-lib");
+    run(cmd, project_dir, args)
+}
+
+/// Like 'rusty_tags', but with the tags of the standard library of the rust
+/// source in 'std_src_dir'.
+fn rusty_tags_with_std(project_dir: &Path, cache_dir: &Path, std_src_dir: &Path, args: &[&str]) -> String {
+    let mut cmd = rusty_tags_command(project_dir, cache_dir);
+    cmd.env("RUST_SRC_PATH", std_src_dir);
+    run(cmd, project_dir, args)
+}
+
+fn rusty_tags_command(project_dir: &Path, cache_dir: &Path) -> Command {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_rusty-tags"));
+    cmd.current_dir(project_dir)
+       .arg("--ctags-exe").arg(fixtures_dir().join("bin").join("ctags"))
+       .arg("--cache-dir").arg(cache_dir)
+       .args(["--no-fetch", "--quiet", "--color", "never"]);
+
+    cmd
+}
+
+/// Runs 'cmd' with 'args' and returns its stdout.
+fn run(mut cmd: Command, project_dir: &Path, args: &[&str]) -> String {
+    let output = cmd.args(args).output().unwrap();
+    assert!(output.status.success(), "rusty-tags {:?} failed in '{}':\n{}",
+            args, project_dir.display(), String::from_utf8_lossy(&output.stderr));
+
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// The sorted names of the tags of the vi tags file 'file'.
+fn tag_names(file: &Path) -> Vec<String> {
+    let tags = fs::read_to_string(file).unwrap_or_else(|err| panic!("Couldn't read '{}': {}", file.display(), err));
+    let mut names: Vec<_> = tags.lines()
+        .filter(|l| ! l.starts_with("!_"))
+        .map(|l| l.split('\t').next().unwrap().to_string())
+        .collect();
+
+    names.sort();
+    names
+}
+
+#[test]
+fn workspace_with_an_inherited_dependency() {
+    let dir = test_dir("workspace_with_an_inherited_dependency");
+    let fixture = copy_fixture("inherited", &dir);
+    let workspace = fixture.join("workspace");
+    rusty_tags(&workspace, &dir.join("cache"), &["vi"]);
+
+    assert_eq!(tag_names(&workspace.join("first").join("rusty-tags.vi")), vec!["first_fn", "shared_fn"]);
+    assert_eq!(tag_names(&workspace.join("second").join("rusty-tags.vi")), vec!["second_fn", "shared_fn"]);
+}
+
+#[test]
+fn dependency_of_another_target() {
+    let dir = test_dir("dependency_of_another_target");
+    let app = copy_fixture("target_deps", &dir).join("app");
+    let (cache_dir, tags_file) = (dir.join("cache"), app.join("rusty-tags.vi"));
+
+    // without '--target' only the dependencies of the host are tagged
+    rusty_tags(&app, &cache_dir, &["vi"]);
+    assert_eq!(tag_names(&tags_file), vec!["app_fn"]);
+
+    rusty_tags(&app, &cache_dir, &["vi", "--target", "x86_64-pc-windows-msvc"]);
+    assert_eq!(tag_names(&tags_file), vec!["app_fn", "win_only_fn"]);
+
+    rusty_tags(&app, &cache_dir, &["vi", "--target", "x86_64-unknown-linux-gnu"]);
+    assert_eq!(tag_names(&tags_file), vec!["app_fn"]);
+
+    rusty_tags(&app, &cache_dir, &["vi", "--all-targets"]);
+    assert_eq!(tag_names(&tags_file), vec!["app_fn", "win_only_fn"]);
+}
+
+#[test]
+fn crate_without_dependencies() {
+    let dir = test_dir("crate_without_dependencies");
+    let project = copy_fixture("no_deps", &dir);
+    rusty_tags(&project, &dir.join("cache"), &["vi"]);
+    assert_eq!(tag_names(&project.join("rusty-tags.vi")), vec!["helper", "lonely"]);
+
+    rusty_tags(&project, &dir.join("cache"), &["emacs"]);
+    let etags = fs::read_to_string(project.join("rusty-tags.emacs")).unwrap();
+    assert!(etags.starts_with('\x0c') && etags.contains("lib.rs,"), "{:?}", etags);
+}
+
+/// Writes a rust source in 'dir' with a 'core' library containing the function 'name'.
+fn std_src_dir(dir: &Path, name: &str) -> PathBuf {
+    let src_dir = dir.join(name);
+    fs::create_dir_all(src_dir.join("core")).unwrap();
+    fs::write(src_dir.join("core").join("lib.rs"), format!("pub fn {}() {{}}\n", name)).unwrap();
+    src_dir
+}
+
+#[test]
+fn std_lib_tags_are_in_the_cache_dir() {
+    let dir = test_dir("std_lib_tags_are_in_the_cache_dir");
+    let project = copy_fixture("no_deps", &dir);
+    let (cache_dir, first_src) = (dir.join("cache"), std_src_dir(&dir, "first_core"));
+    rusty_tags_with_std(&project, &cache_dir, &first_src, &["vi"]);
+    assert_eq!(tag_names(&cache_dir.join("rust-std-lib.vi")), vec!["first_core"]);
+    assert_eq!(fs::read_dir(&first_src).unwrap().count(), 1);
+
+    // the tags are recreated for the source of another toolchain
+    let second_src = std_src_dir(&dir, "second_core");
+    rusty_tags_with_std(&project, &cache_dir, &second_src, &["vi"]);
+    assert_eq!(tag_names(&cache_dir.join("rust-std-lib.vi")), vec!["second_core"]);
+}
+
+#[test]
+fn std_only_doesnt_touch_the_project() {
+    let dir = test_dir("std_only_doesnt_touch_the_project");
+    let project = copy_fixture("no_deps", &dir);
+    let (cache_dir, std_src) = (dir.join("cache"), std_src_dir(&dir, "std_core"));
+    let mut cmd = rusty_tags_command(&project, &cache_dir);
+    cmd.env("RUST_SRC_PATH", &std_src);
+    run(cmd, &project, &["vi", "emacs", "--std-only"]);
+
+    assert_eq!(tag_names(&cache_dir.join("rust-std-lib.vi")), vec!["std_core"]);
+    assert!(cache_dir.join("rust-std-lib.emacs").is_file());
+    assert!(! project.join("rusty-tags.vi").exists() && ! project.join("target").exists());
+}
+
+#[test]
+fn std_tags_path_is_in_the_cache_dir() {
+    let dir = test_dir("std_tags_path_is_in_the_cache_dir");
+    let cache_dir = dir.join("cache");
+    let paths = run(rusty_tags_command(&dir, &cache_dir), &dir, &["--print-std-tags-path"]);
+    assert_eq!(paths, format!("{}\n{}\n", cache_dir.join("rust-std-lib.vi").display(), cache_dir.join("rust-std-lib.emacs").display()));
+
+    let paths = run(rusty_tags_command(&dir, &cache_dir), &dir, &["--print-std-tags-path", "emacs"]);
+    assert_eq!(paths, format!("{}\n", cache_dir.join("rust-std-lib.emacs").display()));
+}
+
+/// The names of the cached vi tags files of 'krate' in 'cache_dir'.
+fn cached_vi_tags_files(cache_dir: &Path, krate: &str) -> Vec<String> {
+    let mut files: Vec<_> = fs::read_dir(cache_dir.join("cache")).unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|f| f.starts_with(&format!("{}-", krate)) && f.ends_with(".vi"))
+        .collect();
+
+    files.sort();
+    files
+}
+
+#[test]
+fn versions_of_a_crate_are_cached_separately() {
+    let dir = test_dir("versions_of_a_crate_are_cached_separately");
+    let fixture = copy_fixture("versions", &dir);
+    let cache_dir = dir.join("cache");
+    rusty_tags(&fixture.join("old_user"), &cache_dir, &["vi"]);
+    rusty_tags(&fixture.join("new_user"), &cache_dir, &["vi"]);
+
+    let files = cached_vi_tags_files(&cache_dir, "versioned");
+    assert_eq!(files.len(), 2, "{:?}", files);
+    assert!(files[0].starts_with("versioned-0.1.0-") && files[1].starts_with("versioned-0.2.0-"), "{:?}", files);
+    assert_eq!(tag_names(&cache_dir.join("cache").join(&files[0])), vec!["old_api"]);
+    assert_eq!(tag_names(&cache_dir.join("cache").join(&files[1])), vec!["new_api"]);
+
+    assert_eq!(tag_names(&fixture.join("old_user").join("rusty-tags.vi")), vec!["old_api", "old_user_fn"]);
+    assert_eq!(tag_names(&fixture.join("new_user").join("rusty-tags.vi")), vec!["new_api", "new_user_fn"]);
+
+    // a new version in the same directory doesn't replace the cached tags of the old one
+    let versioned = fixture.join("versioned-0.1");
+    let manifest = fs::read_to_string(versioned.join("Cargo.toml")).unwrap();
+    fs::write(versioned.join("Cargo.toml"), manifest.replace("0.1.0", "0.3.0")).unwrap();
+    fs::write(versioned.join("src").join("lib.rs"), "pub fn newest_api() {}\n").unwrap();
+    rusty_tags(&fixture.join("old_user"), &cache_dir, &["vi"]);
+
+    let new_files = cached_vi_tags_files(&cache_dir, "versioned");
+    assert_eq!(new_files.len(), 3, "{:?}", new_files);
+    assert_eq!(&new_files[..2], &files[..]);
+    assert!(new_files[2].starts_with("versioned-0.3.0-"), "{:?}", new_files);
+    assert_eq!(tag_names(&cache_dir.join("cache").join(&files[0])), vec!["old_api"]);
+    assert_eq!(tag_names(&fixture.join("old_user").join("rusty-tags.vi")), vec!["newest_api", "old_user_fn"]);
+}
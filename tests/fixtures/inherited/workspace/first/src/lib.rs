@@ -0,0 +1 @@
+pub fn first_fn() {}
@@ -0,0 +1 @@
+pub fn second_fn() {}
@@ -0,0 +1,3 @@
+pub fn lonely() {}
+
+fn helper() {}
@@ -0,0 +1 @@
+pub fn app_fn() {}
@@ -0,0 +1 @@
+pub fn win_only_fn() {}